@@ -1,6 +1,9 @@
-use chronicle_primitives::Config;
+use chronicle_primitives::{
+    db::{create_db_instance, migrations::run_migrations, validate_identifier},
+    Config,
+};
 use chronicle_tasks::{indexer::IndexerTask, server::ServerTask, spawn_tasks};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use toml::from_str;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -10,6 +13,18 @@ use tracing_subscriber::util::SubscriberInitExt;
 pub struct CliConfig {
     #[arg(short, long)]
     pub config_path: String,
+    /// Drops the stored events and checkpoint of every indexer and re-indexes from their configured block,
+    /// only for this run; the next start resumes from the checkpoint again
+    #[arg(long)]
+    pub rebuild: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Applies the pending database migrations of every configured indexer and exits
+    Migrate,
 }
 
 /// Main entry point for the CLI
@@ -18,10 +33,14 @@ pub struct CliConfig {
 /// Listens for a ctrl-c signal and shuts down all components when received.
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     setup()?;
-    let config = CliConfig::parse();
-    let config_str = std::fs::read_to_string(&config.config_path)?;
+    let cli_config = CliConfig::parse();
+    let config_str = std::fs::read_to_string(&cli_config.config_path)?;
     let config: Config = from_str(&config_str)?;
 
+    if let Some(Command::Migrate) = cli_config.command {
+        return migrate(&config).await;
+    }
+
     // server config
     let server_config = config.clone().server;
     //indexer config
@@ -29,10 +48,10 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     tracing::info!("Starting Chronicle with config: {:?}", config.clone());
 
-    let mut tasks = vec![ServerTask::new(server_config).boxed()];
+    let mut tasks = vec![ServerTask::new(server_config, indexer_configs.clone()).boxed()];
 
     for indexer_config in indexer_configs {
-        tasks.push(IndexerTask::new(indexer_config).boxed());
+        tasks.push(IndexerTask::new(indexer_config, cli_config.rebuild).boxed());
     }
 
     spawn_tasks(tasks, tokio::signal::ctrl_c()).await;
@@ -40,6 +59,24 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Applies the pending migrations for every indexer table in the config, without starting any task
+pub async fn migrate(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    for indexer_config in &config.indexer {
+        // The indexer name is also the name of its event table
+        validate_identifier(&indexer_config.event_name)?;
+        let mut client = create_db_instance(&indexer_config.db_url).await?;
+        let applied = run_migrations(&mut client, &[&indexer_config.event_name]).await?;
+
+        tracing::info!(
+            "Applied {} pending migration(s) for indexer {}",
+            applied,
+            indexer_config.event_name
+        );
+    }
+
+    Ok(())
+}
+
 // this function is for setting up the logging process
 pub fn setup() -> Result<(), anyhow::Error> {
     let filter =
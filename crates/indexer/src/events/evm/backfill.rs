@@ -0,0 +1,414 @@
+use std::{
+    future::Future,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use super::utils::LogDecoder;
+use alloy::{
+    providers::{Provider, RootProvider},
+    rpc::types::eth::Filter,
+    transports::Transport,
+};
+use chronicle_primitives::{db::store_events_with_checkpoint, indexer::ChronicleEvent};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// This is the default number of blocks requested by a single `eth_getLogs` call
+pub const DEFAULT_BACKFILL_CHUNK_SIZE: u64 = 2_000;
+
+/// This is the default number of block chunks fetched concurrently
+pub const DEFAULT_BACKFILL_WORKERS: usize = 1;
+
+/// This is the number of windows in a row that must succeed before the block window grows again
+const WINDOW_GROWTH_SUCCESSES: u32 = 4;
+
+/// Fragments of the errors returned by providers that reject a query for its range or result size
+const RANGE_TOO_LARGE_ERRORS: &[&str] = &[
+    "too many results",
+    "query returned more than",
+    "block range",
+    "range is too large",
+    "response size",
+    "limit exceeded",
+    "log response size exceeded",
+];
+
+/// This configures how historical events are fetched
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillConfig {
+    /// This is the largest number of blocks requested by a single `eth_getLogs` call
+    pub chunk_size: u64,
+    /// This is the number of block chunks fetched concurrently
+    pub workers: usize,
+    /// This is the largest number of `eth_getLogs` calls a single worker makes per second, None is unlimited
+    pub requests_per_second: Option<u32>,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_BACKFILL_CHUNK_SIZE,
+            workers: DEFAULT_BACKFILL_WORKERS,
+            requests_per_second: None,
+        }
+    }
+}
+
+impl BackfillConfig {
+    /// Splits `blocks` into consecutive chunks of at most `chunk_size` blocks
+    pub fn chunks(&self, blocks: RangeInclusive<u64>) -> Vec<RangeInclusive<u64>> {
+        let (mut next_block, to_block) = blocks.into_inner();
+        let chunk_size = self.chunk_size.max(1);
+        let mut chunks = Vec::new();
+        while next_block <= to_block {
+            let chunk_end = next_block.saturating_add(chunk_size - 1).min(to_block);
+            chunks.push(next_block..=chunk_end);
+            if chunk_end == u64::MAX {
+                break;
+            }
+            next_block = chunk_end + 1;
+        }
+
+        chunks
+    }
+}
+
+/// This paces the requests of a single backfill worker
+#[derive(Debug)]
+pub struct RequestBudget {
+    /// This is the minimum time between two requests
+    interval: Duration,
+    /// This is the time the last request was allowed
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl RequestBudget {
+    pub fn new(requests_per_second: Option<u32>) -> Self {
+        let interval = match requests_per_second {
+            Some(requests_per_second) if requests_per_second > 0 => {
+                Duration::from_secs(1) / requests_per_second
+            }
+            _ => Duration::ZERO,
+        };
+
+        Self {
+            interval,
+            last_request: Mutex::new(None),
+        }
+    }
+
+    /// Waits until the worker is allowed to make another request
+    pub async fn acquire(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last_request) = *last_request {
+            let next_request = last_request + self.interval;
+            let now = Instant::now();
+            if next_request > now {
+                tokio::time::sleep(next_request - now).await;
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+}
+
+/// This sizes the block windows of a backfill, it shrinks when the provider rejects a window
+/// and grows back to its maximum as windows succeed
+#[derive(Debug, Clone, Copy)]
+pub struct BlockWindow {
+    size: u64,
+    max_size: u64,
+    /// This is the number of windows that succeeded since the window last changed size
+    successes: u32,
+}
+
+impl BlockWindow {
+    pub fn new(max_size: u64) -> Self {
+        let max_size = max_size.max(1);
+        Self {
+            size: max_size,
+            max_size,
+            successes: 0,
+        }
+    }
+
+    /// Returns the last block of the window starting at `from_block`, never past `to_block`
+    pub fn end(&self, from_block: u64, to_block: u64) -> u64 {
+        from_block.saturating_add(self.size - 1).min(to_block)
+    }
+
+    /// Halves the window, returns false when it is already a single block
+    pub fn shrink(&mut self) -> bool {
+        if self.size == 1 {
+            return false;
+        }
+        self.size = (self.size / 2).max(1);
+        self.successes = 0;
+
+        true
+    }
+
+    /// Doubles the window, up to its maximum size
+    pub fn grow(&mut self) {
+        self.size = self.size.saturating_mul(2).min(self.max_size);
+        self.successes = 0;
+    }
+
+    /// Records a window that succeeded, the window only grows after `WINDOW_GROWTH_SUCCESSES` of them in a row
+    /// so it does not keep growing back into the size the provider just rejected
+    pub fn succeeded(&mut self) {
+        self.successes += 1;
+        if self.successes >= WINDOW_GROWTH_SUCCESSES {
+            self.grow();
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Returns true when the provider rejected a query because its block range or result set was too large
+pub fn is_range_too_large(error: &anyhow::Error) -> bool {
+    let message = format!("{error:#}").to_lowercase();
+
+    RANGE_TOO_LARGE_ERRORS
+        .iter()
+        .any(|fragment| message.contains(fragment))
+}
+
+/// This function indexes the events matching `filter` emitted in the `blocks` range.
+/// The range is split into chunks fetched by `workers` concurrent workers, each paced by its own request budget.
+/// The workers share one block window, so a window the provider rejected is not tried again for every chunk.
+/// Chunks are written in block order, each chunk's events are committed together with the checkpoint,
+/// so an interrupted backfill resumes from the last committed chunk.
+pub async fn backfill_events<T: Transport + Clone>(
+    provider: &RootProvider<T>,
+    decoder: &LogDecoder,
+    filter: &Filter,
+    blocks: RangeInclusive<u64>,
+    config: &BackfillConfig,
+    db_client: &mut tokio_postgres::Client,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let workers = config.workers.max(1);
+    let budgets: Vec<RequestBudget> = (0..workers)
+        .map(|_| RequestBudget::new(config.requests_per_second))
+        .collect();
+    let window = Mutex::new(BlockWindow::new(config.chunk_size));
+
+    // At most `workers` consecutive chunks are in flight, so each worker's budget paces a single chunk at a time
+    let mut chunks = stream::iter(config.chunks(blocks).into_iter().enumerate())
+        .map(|(index, chunk)| {
+            let (budget, window) = (&budgets[index % workers], &window);
+            async move {
+                let events = get_chunk_events(
+                    provider,
+                    decoder,
+                    filter,
+                    chunk.clone(),
+                    window,
+                    budget,
+                    name,
+                )
+                .await?;
+                Ok::<_, anyhow::Error>((chunk, events))
+            }
+        })
+        .buffered(workers);
+
+    while let Some((chunk, events)) = chunks.try_next().await? {
+        store_events_with_checkpoint(&events, *chunk.end(), db_client, name).await?;
+        info!(
+            "Indexer {} backfilled blocks {} to {} ({} events)",
+            name,
+            chunk.start(),
+            chunk.end(),
+            events.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// This function fetches the events matching `filter` in a single chunk.
+/// The chunk is walked in block windows that shrink when the provider rejects them.
+async fn get_chunk_events<T: Transport + Clone>(
+    provider: &RootProvider<T>,
+    decoder: &LogDecoder,
+    filter: &Filter,
+    chunk: RangeInclusive<u64>,
+    window: &Mutex<BlockWindow>,
+    budget: &RequestBudget,
+    name: &str,
+) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
+    get_events_in_windows(chunk, window, name, |from_block, to_block| async move {
+        budget.acquire().await;
+        get_window_events(provider, decoder, filter, from_block, to_block).await
+    })
+    .await
+}
+
+/// This function walks `chunk` in windows sized by `window`, fetching the events of each window with `fetch`.
+/// A window rejected for its range or result size is retried with a smaller one,
+/// the size reached is kept in `window` for the next chunks.
+async fn get_events_in_windows<F, R>(
+    chunk: RangeInclusive<u64>,
+    window: &Mutex<BlockWindow>,
+    name: &str,
+    mut fetch: F,
+) -> Result<Vec<ChronicleEvent>, anyhow::Error>
+where
+    F: FnMut(u64, u64) -> R,
+    R: Future<Output = Result<Vec<ChronicleEvent>, anyhow::Error>>,
+{
+    let (mut next_block, to_block) = chunk.into_inner();
+    let mut events = Vec::new();
+
+    while next_block <= to_block {
+        let window_end = window.lock().await.end(next_block, to_block);
+        match fetch(next_block, window_end).await {
+            Ok(window_events) => events.extend(window_events),
+            Err(error) if is_range_too_large(&error) => {
+                let mut window = window.lock().await;
+                // Another worker may already have shrunk the window below the rejected one
+                if window.end(next_block, to_block) == window_end && !window.shrink() {
+                    return Err(error);
+                }
+                warn!(
+                    "Indexer {} backfill of blocks {} to {} was rejected, retrying with {} blocks: {:#}",
+                    name,
+                    next_block,
+                    window_end,
+                    window.size(),
+                    error
+                );
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+
+        next_block = window_end + 1;
+        window.lock().await.succeeded();
+    }
+
+    Ok(events)
+}
+
+/// This function fetches the events matching `filter` between `from_block` and `to_block` (both inclusive)
+pub async fn get_window_events<T: Transport + Clone>(
+    provider: &RootProvider<T>,
+    decoder: &LogDecoder,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
+    let filter = filter.clone().from_block(from_block).to_block(to_block);
+    let logs = provider.get_logs(&filter).await?;
+
+    let mut events = Vec::with_capacity(logs.len());
+    for log in logs {
+        events.push(decoder.event(provider, log).await?);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    pub fn test_block_window_is_clamped_to_range() {
+        let window = BlockWindow::new(100);
+
+        assert_eq!(window.end(0, 1_000), 99);
+        assert_eq!(window.end(950, 1_000), 1_000);
+        assert_eq!(BlockWindow::new(0).end(7, 1_000), 7);
+    }
+
+    #[test]
+    pub fn test_block_window_shrinks_and_grows_back() {
+        let mut window = BlockWindow::new(8);
+
+        assert!(window.shrink());
+        assert!(window.shrink());
+        assert!(window.shrink());
+        assert_eq!(window.size(), 1);
+        assert!(!window.shrink());
+
+        window.grow();
+        window.grow();
+        window.grow();
+        window.grow();
+        assert_eq!(window.size(), 8);
+    }
+
+    #[tokio::test]
+    pub async fn test_block_window_is_kept_across_chunks() {
+        let window = Mutex::new(BlockWindow::new(1_000));
+        let requests = std::sync::Mutex::new(Vec::new());
+        // This provider rejects every window of more than 250 blocks
+        let fetch = |from_block: u64, to_block: u64| {
+            requests.lock().unwrap().push(from_block..=to_block);
+            async move {
+                match to_block - from_block + 1 > 250 {
+                    true => Err(anyhow!("query returned more than 10000 results")),
+                    false => Ok(Vec::new()),
+                }
+            }
+        };
+
+        get_events_in_windows(0..=999, &window, "test", &fetch)
+            .await
+            .unwrap();
+        let first_chunk_requests = requests.lock().unwrap().len();
+        get_events_in_windows(1_000..=1_999, &window, "test", &fetch)
+            .await
+            .unwrap();
+        let requests = requests.into_inner().unwrap();
+
+        assert_eq!(
+            &requests[..first_chunk_requests],
+            &[0..=999, 0..=499, 0..=249, 250..=499, 500..=749, 750..=999]
+        );
+        // The second chunk starts from the window learned by the first one instead of the whole chunk
+        assert_eq!(requests[first_chunk_requests], 1_000..=1_499);
+        assert!(requests.len() - first_chunk_requests < first_chunk_requests);
+    }
+
+    #[test]
+    pub fn test_splits_range_into_chunks() {
+        let config = BackfillConfig {
+            chunk_size: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(config.chunks(0..=24), vec![0..=9, 10..=19, 20..=24]);
+        assert_eq!(config.chunks(5..=5), vec![5..=5]);
+    }
+
+    #[tokio::test]
+    pub async fn test_request_budget_paces_requests() {
+        let budget = RequestBudget::new(Some(20));
+        let start = Instant::now();
+        for _ in 0..3 {
+            budget.acquire().await;
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    pub fn test_detects_range_too_large_errors() {
+        assert!(is_range_too_large(&anyhow!(
+            "server returned an error response: error code -32005: query returned more than 10000 results"
+        )));
+        assert!(is_range_too_large(&anyhow!(
+            "error code -32602: Log response size exceeded"
+        )));
+        assert!(!is_range_too_large(&anyhow!("connection reset by peer")));
+    }
+}
@@ -0,0 +1,93 @@
+use alloy::{
+    providers::{Provider, RootProvider},
+    rpc::types::eth::BlockNumberOrTag,
+    transports::Transport,
+};
+use anyhow::{anyhow, bail};
+
+/// This decides how deep a block must be before its events are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Finality {
+    /// Events are stored once their block has this many blocks on top of it, 0 stores them as soon as they are seen
+    Confirmations(u64),
+    /// Events are stored once their block is at or below this block tag, `safe` or `finalized`
+    Tag(BlockNumberOrTag),
+}
+
+impl Default for Finality {
+    fn default() -> Self {
+        Self::Confirmations(0)
+    }
+}
+
+impl Finality {
+    /// Builds the finality from the indexer config, `safe` and `finalized` take precedence over confirmations
+    /// while `latest`, the chain head, is what confirmations are counted from
+    /// params:
+    /// confirmations: u64 - The number of blocks to lag behind the chain head
+    /// finality_tag: Option<&str> - The block tag to follow, `latest`, `safe` or `finalized`
+    pub fn new(confirmations: u64, finality_tag: Option<&str>) -> Result<Self, anyhow::Error> {
+        let Some(finality_tag) = finality_tag else {
+            return Ok(Self::Confirmations(confirmations));
+        };
+
+        match finality_tag.parse::<BlockNumberOrTag>() {
+            Ok(BlockNumberOrTag::Latest) => Ok(Self::Confirmations(confirmations)),
+            Ok(tag @ (BlockNumberOrTag::Safe | BlockNumberOrTag::Finalized)) => Ok(Self::Tag(tag)),
+            _ => bail!(
+                "Invalid finality tag {finality_tag}, expected `latest`, `safe` or `finalized`"
+            ),
+        }
+    }
+
+    /// Returns true when events are stored as soon as they are seen, and may still be reorged away
+    pub fn is_latest(&self) -> bool {
+        *self == Self::Confirmations(0)
+    }
+
+    /// This function returns the highest block whose events are deep enough to be stored
+    pub async fn safe_block<T: Transport + Clone>(
+        &self,
+        provider: &RootProvider<T>,
+    ) -> Result<u64, anyhow::Error> {
+        match self {
+            Self::Confirmations(confirmations) => Ok(provider
+                .get_block_number()
+                .await?
+                .saturating_sub(*confirmations)),
+            Self::Tag(tag) => provider
+                .get_block_by_number(*tag, false)
+                .await?
+                .and_then(|block| block.header.number)
+                .ok_or_else(|| anyhow!("The node returned no {tag} block")),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_finality_from_config() {
+        assert_eq!(Finality::new(0, None).unwrap(), Finality::Confirmations(0));
+        assert_eq!(
+            Finality::new(12, None).unwrap(),
+            Finality::Confirmations(12)
+        );
+        assert_eq!(
+            Finality::new(12, Some("finalized")).unwrap(),
+            Finality::Tag(BlockNumberOrTag::Finalized)
+        );
+        assert_eq!(
+            Finality::new(0, Some("safe")).unwrap(),
+            Finality::Tag(BlockNumberOrTag::Safe)
+        );
+        assert!(Finality::new(0, Some("latest")).unwrap().is_latest());
+        assert_eq!(
+            Finality::new(12, Some("latest")).unwrap(),
+            Finality::Confirmations(12)
+        );
+        assert!(Finality::new(0, Some("pending")).is_err());
+    }
+}
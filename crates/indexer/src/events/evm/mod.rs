@@ -1,70 +1,226 @@
+pub mod backfill;
+pub mod finality;
+pub mod reconnect;
+pub mod reorg;
+pub mod timestamps;
+pub mod transport;
 pub mod utils;
 
-use self::utils::{query_events, subscribe_to_events};
+use self::{
+    backfill::{backfill_events, BackfillConfig},
+    finality::Finality,
+    reorg::{find_fork_block, BlockHashTracker, REORG_TRACKING_DEPTH},
+    utils::{subscribe_to_events, subscribe_to_finalized_events, LogDecoder},
+};
 use alloy::{
-    primitives::{Address, B256},
-    providers::RootProvider,
+    providers::{Provider, RootProvider},
     pubsub::PubSubFrontend,
-    rpc::types::eth::BlockNumberOrTag,
+    rpc::types::eth::{BlockNumberOrTag, Filter},
+    transports::Transport,
 };
 use async_trait::async_trait;
 use chronicle_primitives::{
-    db::{create_new_event_db_table, store_event_to_db},
+    abi::IndexerAbi,
+    db::{
+        checkpoint::{get_checkpoint, rollback_to_block, store_checkpoint},
+        get_recent_block_hashes,
+        migrations::assign_missing_chain_id,
+    },
     interfaces::ChronicleEventIndexer,
 };
+use std::{sync::Arc, time::Duration};
+use tracing::warn;
 
 pub struct EvmEventIndexer {
     /// This is the name if this indexer instance, this is used for the DB table name
     name: String,
+    /// This decides how deep a block must be before its events are stored
+    finality: Finality,
+    /// This configures how historical events are fetched
+    backfill: BackfillConfig,
+    /// This decodes the parameters of the indexed events, it has no events when no ABI is configured
+    abi: Arc<IndexerAbi>,
 }
 
 impl EvmEventIndexer {
-    pub fn new(name: String) -> Self {
-        Self { name }
+    pub fn new(
+        name: String,
+        finality: Finality,
+        backfill: BackfillConfig,
+        abi: Arc<IndexerAbi>,
+    ) -> Self {
+        Self {
+            name,
+            finality,
+            backfill,
+            abi,
+        }
+    }
+
+    /// This function indexes the events from `block_number` onwards by polling `eth_getLogs`,
+    /// it is used with endpoints that can't push new logs such as HTTP endpoints.
+    /// Every poll rolls back orphaned blocks, then backfills up to the latest block deep enough to store.
+    /// returns only on error, so the caller can reconnect
+    pub async fn poll_events<T: Transport + Clone>(
+        &self,
+        provider: RootProvider<T>,
+        filter: Filter,
+        block_number: u64,
+        interval: Duration,
+        db_client: &mut tokio_postgres::Client,
+    ) -> Result<(), anyhow::Error> {
+        let chain_id = provider.get_chain_id().await?;
+        assign_missing_chain_id(db_client, &self.name, chain_id).await?;
+        let decoder = LogDecoder::new(chain_id, self.abi.clone());
+        let mut from_block = block_number;
+
+        loop {
+            if let Some(fork_block) = self.rollback_orphaned_blocks(&provider, db_client).await? {
+                from_block = from_block.min(fork_block);
+            }
+
+            let safe_block = self.finality.safe_block(&provider).await?;
+            if from_block <= safe_block {
+                backfill_events(
+                    &provider,
+                    &decoder,
+                    &filter,
+                    from_block..=safe_block,
+                    &self.backfill,
+                    db_client,
+                    &self.name,
+                )
+                .await?;
+                from_block = safe_block + 1;
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// This function drops the indexed blocks that are no longer part of the canonical chain
+    /// returns the first orphaned block, None when every recently indexed block is canonical
+    async fn rollback_orphaned_blocks<T: Transport + Clone>(
+        &self,
+        provider: &RootProvider<T>,
+        db_client: &mut tokio_postgres::Client,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        let tracker = BlockHashTracker::new(
+            REORG_TRACKING_DEPTH,
+            get_recent_block_hashes(db_client, &self.name, REORG_TRACKING_DEPTH).await?,
+        );
+        let Some(fork_block) = find_fork_block(provider, &tracker).await? else {
+            return Ok(None);
+        };
+
+        warn!(
+            "Indexer {} found orphaned blocks from block {}, re-indexing them",
+            self.name, fork_block
+        );
+        rollback_to_block(db_client, &self.name, fork_block).await?;
+
+        Ok(Some(fork_block))
+    }
+
+    /// This function indexes the events matching `filter` from `from_block` onwards,
+    /// the history is backfilled first and the chain is then followed without leaving a gap
+    async fn index_from_block(
+        &self,
+        provider: RootProvider<PubSubFrontend>,
+        filter: Filter,
+        from_block: u64,
+        db_client: &mut tokio_postgres::Client,
+    ) -> Result<(), anyhow::Error> {
+        let decoder = LogDecoder::new(provider.get_chain_id().await?, self.abi.clone());
+        if self.finality.is_latest() {
+            return subscribe_to_events(
+                provider,
+                &decoder,
+                filter,
+                from_block,
+                &self.backfill,
+                db_client,
+                &self.name,
+            )
+            .await;
+        }
+
+        // Finalized events are followed from the checkpoint, so the backfill only has to reach the safe block once
+        let safe_block = self.finality.safe_block(&provider).await?;
+        if from_block <= safe_block {
+            backfill_events(
+                &provider,
+                &decoder,
+                &filter,
+                from_block..=safe_block,
+                &self.backfill,
+                db_client,
+                &self.name,
+            )
+            .await?;
+        } else if from_block > 0 {
+            store_checkpoint(db_client, &self.name, from_block - 1).await?;
+        }
+
+        subscribe_to_finalized_events(
+            provider,
+            &decoder,
+            filter,
+            self.finality,
+            self.backfill,
+            db_client,
+            &self.name,
+        )
+        .await
     }
 }
 
 #[async_trait]
 impl ChronicleEventIndexer for EvmEventIndexer {
     type SubProvider = RootProvider<PubSubFrontend>;
-    type ContractAddress = Address;
-    type EventSignature = B256;
+    type EventFilter = Filter;
     type BlockNumber = BlockNumberOrTag;
 
     // TODO: This should be renamed to query then subscribe events
     async fn query_events(
         &self,
         provider: Self::SubProvider,
-        addr: Self::ContractAddress,
-        event_sig: Self::EventSignature,
-        block_number: Self::BlockNumber,
+        filter: Self::EventFilter,
+        mut block_number: Self::BlockNumber,
         db_client: &mut tokio_postgres::Client,
     ) -> Result<(), anyhow::Error> {
-        create_new_event_db_table(db_client, &self.name).await?;
-        // Query existing events from the specified block number
-        let events = query_events(provider.clone(), addr, event_sig, block_number).await?;
+        // Events stored before chain ids were recorded belong to this indexer's chain
+        let chain_id = provider.get_chain_id().await?;
+        assign_missing_chain_id(db_client, &self.name, chain_id).await?;
 
-        // Store all this event is the database
-        for event in events {
-            store_event_to_db(&event, db_client, &self.name).await?;
+        // Blocks indexed before a restart may have been reorged while the indexer was down
+        if let Some(fork_block) = self.rollback_orphaned_blocks(&provider, db_client).await? {
+            block_number = fork_block.into();
         }
 
-        // Now subsbribing the events
-        self.subscribe_to_events(provider, vec![addr], event_sig, db_client)
-            .await?;
-
-        Ok(())
+        // Query existing events from the specified block number, then subscribe to new events
+        self.index_from_block(
+            provider,
+            filter,
+            block_number.as_number().unwrap_or_default(),
+            db_client,
+        )
+        .await
     }
 
     async fn subscribe_to_events(
         &self,
         provider: Self::SubProvider,
-        addr: Vec<Self::ContractAddress>,
-        event_sig: Self::EventSignature,
+        filter: Self::EventFilter,
         db_client: &mut tokio_postgres::Client,
     ) -> Result<(), anyhow::Error> {
-        subscribe_to_events(provider, addr, event_sig, db_client, &self.name).await;
+        // Without a checkpoint only events from the next block onwards are indexed
+        let from_block = match get_checkpoint(db_client, &self.name).await? {
+            Some(block_number) => block_number + 1,
+            None => self.finality.safe_block(&provider).await? + 1,
+        };
 
-        Ok(())
+        self.index_from_block(provider, filter, from_block, db_client)
+            .await
     }
 }
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+/// This is the delay before the first reconnect attempt
+pub const DEFAULT_RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// This is the longest delay between two reconnect attempts
+pub const DEFAULT_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// This spaces the reconnect attempts of an indexer, doubling the delay after every failed attempt
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    /// This is the number of reconnect attempts since the last reset
+    attempt: u32,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECONNECT_INITIAL_DELAY, DEFAULT_RECONNECT_MAX_DELAY)
+    }
+}
+
+impl ReconnectBackoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            attempt: 0,
+        }
+    }
+
+    /// Returns the delay before the next reconnect attempt, up to the maximum delay
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.max_delay);
+        self.attempt = self.attempt.saturating_add(1);
+
+        delay
+    }
+
+    /// Returns the number of reconnect attempts since the last reset
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Starts over from the initial delay, used once the indexer made progress again
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_backoff_doubles_up_to_max_delay() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        let delays: Vec<u64> = (0..6).map(|_| backoff.next_delay().as_secs()).collect();
+
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+        assert_eq!(backoff.attempt(), 6);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    pub fn test_backoff_does_not_overflow() {
+        let mut backoff = ReconnectBackoff::default();
+        for _ in 0..100 {
+            backoff.next_delay();
+        }
+
+        assert_eq!(backoff.next_delay(), DEFAULT_RECONNECT_MAX_DELAY);
+    }
+}
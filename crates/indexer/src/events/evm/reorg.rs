@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use alloy::{
+    primitives::B256,
+    providers::{Provider, RootProvider},
+    rpc::types::eth::BlockNumberOrTag,
+    transports::Transport,
+};
+
+/// This is the number of recent blocks whose hashes are kept to detect reorgs
+pub const REORG_TRACKING_DEPTH: usize = 128;
+
+/// This keeps the hashes of the most recent blocks that events were indexed from
+#[derive(Debug, Clone)]
+pub struct BlockHashTracker {
+    /// This is the maximum number of blocks tracked, older blocks are forgotten first
+    depth: usize,
+    /// This maps the tracked block numbers to their hashes
+    hashes: BTreeMap<u64, B256>,
+}
+
+impl BlockHashTracker {
+    pub fn new(depth: usize, blocks: impl IntoIterator<Item = (u64, B256)>) -> Self {
+        let mut tracker = Self {
+            depth,
+            hashes: BTreeMap::new(),
+        };
+        for (block_number, block_hash) in blocks {
+            tracker.insert(block_number, block_hash);
+        }
+
+        tracker
+    }
+
+    /// Returns true when a block contradicts the tracked chain, either because another hash
+    /// was seen for the same block number or because a later block was already seen
+    pub fn is_reorg(&self, block_number: u64, block_hash: B256) -> bool {
+        match self.hashes.get(&block_number) {
+            Some(tracked_hash) => *tracked_hash != block_hash,
+            None => self
+                .latest_block()
+                .is_some_and(|latest_block| latest_block > block_number),
+        }
+    }
+
+    /// Records the hash of a block, forgetting the oldest block once `depth` is exceeded
+    pub fn insert(&mut self, block_number: u64, block_hash: B256) {
+        self.hashes.insert(block_number, block_hash);
+        while self.hashes.len() > self.depth {
+            self.hashes.pop_first();
+        }
+    }
+
+    /// Forgets every block from `block_number` onwards
+    pub fn rollback(&mut self, block_number: u64) {
+        self.hashes.split_off(&block_number);
+    }
+
+    /// Returns the most recent tracked block number
+    pub fn latest_block(&self) -> Option<u64> {
+        self.hashes.keys().next_back().copied()
+    }
+}
+
+/// This function finds the first orphaned block by comparing the tracked hashes with the canonical chain.
+/// It walks back from the most recent tracked block and stops at the first block that is still canonical.
+/// returns None when every tracked block is canonical
+pub async fn find_fork_block<T: Transport + Clone>(
+    provider: &RootProvider<T>,
+    tracker: &BlockHashTracker,
+) -> Result<Option<u64>, anyhow::Error> {
+    let mut fork_block = None;
+    for (block_number, block_hash) in tracker.hashes.iter().rev() {
+        let canonical_hash = provider
+            .get_block_by_number(BlockNumberOrTag::Number(*block_number), false)
+            .await?
+            .and_then(|block| block.header.hash);
+        if canonical_hash == Some(*block_hash) {
+            break;
+        }
+        fork_block = Some(*block_number);
+    }
+
+    Ok(fork_block)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_detects_conflicting_block_hash() {
+        let tracker = BlockHashTracker::new(REORG_TRACKING_DEPTH, [(10, B256::repeat_byte(1))]);
+
+        assert!(!tracker.is_reorg(10, B256::repeat_byte(1)));
+        assert!(tracker.is_reorg(10, B256::repeat_byte(2)));
+        assert!(!tracker.is_reorg(11, B256::repeat_byte(3)));
+    }
+
+    #[test]
+    pub fn test_detects_block_older_than_tip() {
+        let tracker = BlockHashTracker::new(
+            REORG_TRACKING_DEPTH,
+            [(10, B256::repeat_byte(1)), (12, B256::repeat_byte(2))],
+        );
+
+        assert!(tracker.is_reorg(11, B256::repeat_byte(3)));
+    }
+
+    #[test]
+    pub fn test_forgets_blocks_beyond_depth_and_after_rollback() {
+        let mut tracker =
+            BlockHashTracker::new(2, (1..=4).map(|n| (n, B256::repeat_byte(n as u8))));
+
+        assert_eq!(tracker.hashes.len(), 2);
+        assert_eq!(tracker.latest_block(), Some(4));
+
+        tracker.rollback(4);
+        assert_eq!(tracker.latest_block(), Some(3));
+        assert!(!tracker.is_reorg(4, B256::repeat_byte(9)));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use alloy::{
+    primitives::B256,
+    providers::{Provider, RootProvider},
+    transports::Transport,
+};
+use chronicle_primitives::indexer::ChronicleEvent;
+
+/// This is the number of recent blocks whose timestamps are kept
+pub const BLOCK_TIMESTAMP_CACHE_SIZE: usize = 1_024;
+
+/// This keeps the timestamps of recently indexed blocks.
+/// Most nodes return logs without `blockTimestamp`, so it is read from the block header, once per block.
+#[derive(Debug, Clone, Default)]
+pub struct BlockTimestamps {
+    /// This maps the cached block numbers to the hash their timestamp was read for and the timestamp
+    blocks: Arc<Mutex<BTreeMap<u64, (B256, u64)>>>,
+}
+
+impl BlockTimestamps {
+    /// This function sets the block timestamp of an event the node returned without one,
+    /// reading the header of its block the first time the block is seen.
+    /// The timestamp is left empty when the node no longer knows the block, e.g. because it was reorged away.
+    pub async fn fill<T: Transport + Clone>(
+        &self,
+        provider: &RootProvider<T>,
+        event: &mut ChronicleEvent,
+    ) -> Result<(), anyhow::Error> {
+        self.fill_with(event, |block_hash| async move {
+            let block = provider.get_block_by_hash(block_hash, false).await?;
+            Ok(block.map(|block| block.header.timestamp))
+        })
+        .await
+    }
+
+    /// This function sets the block timestamp of an event without one, fetching it with `fetch` when it is not cached
+    async fn fill_with<F, R>(
+        &self,
+        event: &mut ChronicleEvent,
+        fetch: F,
+    ) -> Result<(), anyhow::Error>
+    where
+        F: FnOnce(B256) -> R,
+        R: Future<Output = Result<Option<u64>, anyhow::Error>>,
+    {
+        if event.block_timestamp.is_none() {
+            event.block_timestamp = self
+                .get_or_fetch(event.block_number, event.block_hash, fetch)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// This function returns the cached timestamp of a block, fetching it with `fetch` when it is not cached.
+    /// A block is cached with its hash, so a block replaced by a reorg is fetched again.
+    async fn get_or_fetch<F, R>(
+        &self,
+        block_number: u64,
+        block_hash: B256,
+        fetch: F,
+    ) -> Result<Option<u64>, anyhow::Error>
+    where
+        F: FnOnce(B256) -> R,
+        R: Future<Output = Result<Option<u64>, anyhow::Error>>,
+    {
+        if let Some((hash, timestamp)) = self.blocks.lock().unwrap().get(&block_number) {
+            if *hash == block_hash {
+                return Ok(Some(*timestamp));
+            }
+        }
+
+        let Some(timestamp) = fetch(block_hash).await? else {
+            return Ok(None);
+        };
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.insert(block_number, (block_hash, timestamp));
+        // Blocks are indexed in ascending order, so the oldest ones are not asked for again
+        while blocks.len() > BLOCK_TIMESTAMP_CACHE_SIZE {
+            blocks.pop_first();
+        }
+
+        Ok(Some(timestamp))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use alloy::{
+        primitives::{Address, Bytes, LogData},
+        rpc::types::eth::Log,
+    };
+
+    #[tokio::test]
+    pub async fn test_block_timestamp_is_fetched_once_per_block() {
+        let timestamps = BlockTimestamps::default();
+        let fetched = Mutex::new(Vec::new());
+        let fetch = |block_hash: B256| {
+            fetched.lock().unwrap().push(block_hash);
+            async move { Ok(Some(1_714_000_000 + block_hash[0] as u64)) }
+        };
+
+        for block_hash in [
+            B256::repeat_byte(1),
+            B256::repeat_byte(1),
+            B256::repeat_byte(2),
+        ] {
+            timestamps
+                .get_or_fetch(10, block_hash, fetch)
+                .await
+                .unwrap();
+        }
+        let timestamp = timestamps
+            .get_or_fetch(10, B256::repeat_byte(2), fetch)
+            .await
+            .unwrap();
+
+        assert_eq!(timestamp, Some(1_714_000_002));
+        // The block replaced by a reorg is read again, the same block is not
+        assert_eq!(
+            fetched.into_inner().unwrap(),
+            vec![B256::repeat_byte(1), B256::repeat_byte(2)]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_log_without_timestamp_gets_the_block_timestamp() {
+        let timestamps = BlockTimestamps::default();
+        let log = |block_timestamp: Option<u64>| Log {
+            inner: alloy::primitives::Log {
+                address: Address::ZERO,
+                data: LogData::new_unchecked(Vec::new(), Bytes::new()),
+            },
+            block_hash: Some(B256::repeat_byte(1)),
+            block_number: Some(10),
+            block_timestamp,
+            ..Default::default()
+        };
+        let fetch = |_| async { Ok(Some(1_714_000_000)) };
+
+        let mut event = ChronicleEvent::from(log(None));
+        timestamps.fill_with(&mut event, fetch).await.unwrap();
+        assert_eq!(event.block_timestamp, Some(1_714_000_000));
+
+        // A timestamp returned with the log is kept
+        let mut event = ChronicleEvent::from(log(Some(1_700_000_000)));
+        timestamps
+            .fill_with(&mut event, |_| async { panic!("the block was read") })
+            .await
+            .unwrap();
+        assert_eq!(event.block_timestamp, Some(1_700_000_000));
+    }
+
+    #[tokio::test]
+    pub async fn test_unknown_block_has_no_timestamp() {
+        let timestamps = BlockTimestamps::default();
+        let timestamp = timestamps
+            .get_or_fetch(10, B256::repeat_byte(1), |_| async { Ok(None) })
+            .await
+            .unwrap();
+
+        assert_eq!(timestamp, None);
+        assert!(timestamps.blocks.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::bail;
+
+/// This is the default time between two polls of an endpoint that can't push new logs
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(2_000);
+
+/// This is the transport used to reach an RPC endpoint, it is chosen from the scheme of the endpoint url
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcTransport {
+    /// `ws://` and `wss://` endpoints push new logs over a subscription
+    Ws,
+    /// `http://` and `https://` endpoints are polled with `eth_getLogs`
+    Http,
+    /// Unix socket paths, and `ipc://` urls, reach a co-located node and push new logs like websockets
+    Ipc,
+}
+
+impl RpcTransport {
+    /// Picks the transport of an RPC endpoint from the scheme of its url
+    /// params:
+    /// rpc_url: &str - The url of the RPC endpoint
+    pub fn from_url(rpc_url: &str) -> Result<Self, anyhow::Error> {
+        let scheme = rpc_url
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+
+        match scheme.as_deref() {
+            Some("ws" | "wss") => Ok(Self::Ws),
+            Some("http" | "https") => Ok(Self::Http),
+            Some("ipc") => Ok(Self::Ipc),
+            None if rpc_url.starts_with('/') || rpc_url.ends_with(".ipc") => Ok(Self::Ipc),
+            _ => bail!(
+                "Unsupported rpc url {rpc_url}, expected a ws(s):// or http(s):// url or an ipc socket path"
+            ),
+        }
+    }
+}
+
+/// Returns the socket path of an ipc rpc url, the `ipc://` prefix is optional
+pub fn ipc_path(rpc_url: &str) -> PathBuf {
+    PathBuf::from(rpc_url.strip_prefix("ipc://").unwrap_or(rpc_url))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_transport_from_url_scheme() {
+        assert_eq!(
+            RpcTransport::from_url("wss://eth.merkle.io").unwrap(),
+            RpcTransport::Ws
+        );
+        assert_eq!(
+            RpcTransport::from_url("ws://localhost:8546").unwrap(),
+            RpcTransport::Ws
+        );
+        assert_eq!(
+            RpcTransport::from_url("HTTPS://eth.merkle.io").unwrap(),
+            RpcTransport::Http
+        );
+        assert_eq!(
+            RpcTransport::from_url("http://localhost:8545").unwrap(),
+            RpcTransport::Http
+        );
+        assert_eq!(
+            RpcTransport::from_url("/var/run/reth/reth.ipc").unwrap(),
+            RpcTransport::Ipc
+        );
+        assert_eq!(
+            RpcTransport::from_url("ipc:///tmp/geth.ipc").unwrap(),
+            RpcTransport::Ipc
+        );
+        assert!(RpcTransport::from_url("localhost:8545").is_err());
+        assert!(RpcTransport::from_url("ftp://localhost").is_err());
+    }
+
+    #[test]
+    pub fn test_ipc_path_strips_scheme() {
+        assert_eq!(
+            ipc_path("ipc:///tmp/geth.ipc"),
+            PathBuf::from("/tmp/geth.ipc")
+        );
+        assert_eq!(ipc_path("/tmp/geth.ipc"), PathBuf::from("/tmp/geth.ipc"));
+    }
+}
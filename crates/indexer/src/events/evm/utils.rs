@@ -1,52 +1,360 @@
+use super::{
+    backfill::{backfill_events, BackfillConfig},
+    finality::Finality,
+    reorg::{find_fork_block, BlockHashTracker, REORG_TRACKING_DEPTH},
+    timestamps::BlockTimestamps,
+};
 use alloy::{
-    dyn_abi::{DecodedEvent, DynSolEvent, DynSolType},
+    dyn_abi::{DecodedEvent, DynSolEvent, DynSolType, Specifier},
+    json_abi::Event,
     primitives::{Address, Bytes, LogData, B256},
     providers::{Provider, RootProvider},
     pubsub::PubSubFrontend,
-    rpc::types::eth::{BlockNumberOrTag, Filter},
+    rpc::types::eth::{BlockNumberOrTag, Filter, Log},
+    transports::Transport,
+};
+use anyhow::{anyhow, bail};
+use chronicle_primitives::{
+    abi::{DecodedParam, IndexerAbi},
+    db::{
+        checkpoint::{get_checkpoint, rollback_to_block, store_checkpoint},
+        delete_event_from_db, get_recent_block_hashes, store_event_to_db,
+    },
+    indexer::ChronicleEvent,
 };
-use chronicle_primitives::{db::store_event_to_db, indexer::ChronicleEvent};
 use futures_util::stream::StreamExt;
+use std::{ops::RangeInclusive, sync::Arc};
+use tracing::warn;
 
-pub async fn query_events(
-    provider: RootProvider<PubSubFrontend>,
-    addr: Address,
-    event_sig: B256,
-    block_number: BlockNumberOrTag,
-) -> Result<Vec<ChronicleEvent>, anyhow::Error> {
-    let filter = Filter::new()
-        .address(addr)
-        .event_signature(event_sig)
-        .from_block(block_number);
-    let log = provider.get_logs(&filter).await?;
-    let chronicle_logs: Vec<ChronicleEvent> = log.into_iter().map(|log| log.into()).collect();
-
-    Ok(chronicle_logs)
+/// This turns the logs of an indexer into events, decoding their parameters when the indexer has an ABI
+#[derive(Debug, Clone)]
+pub struct LogDecoder {
+    /// This is the id of the chain the logs are emitted on, logs do not carry it themselves
+    pub chain_id: u64,
+    pub abi: Arc<IndexerAbi>,
+    /// These are the timestamps of the recent blocks, for the logs returned without one
+    timestamps: BlockTimestamps,
+}
+
+impl LogDecoder {
+    pub fn new(chain_id: u64, abi: Arc<IndexerAbi>) -> Self {
+        Self {
+            chain_id,
+            abi,
+            timestamps: BlockTimestamps::default(),
+        }
+    }
+
+    /// Returns the event of `log`, a log that can't be decoded is still returned with its raw topics and data.
+    /// The block timestamp is read from the block header when the node returned the log without it.
+    pub async fn event<T: Transport + Clone>(
+        &self,
+        provider: &RootProvider<T>,
+        log: Log,
+    ) -> Result<ChronicleEvent, anyhow::Error> {
+        let mut event = ChronicleEvent::from(log).with_chain_id(self.chain_id);
+        self.timestamps.fill(provider, &mut event).await?;
+        let Some(abi_event) = event
+            .topics
+            .first()
+            .and_then(|selector| self.abi.event(selector))
+        else {
+            return Ok(event);
+        };
+
+        match self.decode(abi_event, &event) {
+            Ok(decoded) => event.decoded = decoded,
+            Err(e) => warn!(
+                "Could not decode the {} log {}:{}: {e}",
+                abi_event.name, event.transaction_hash, event.log_index
+            ),
+        }
+
+        Ok(event)
+    }
+
+    /// This function decodes the parameters of a log of `abi_event`
+    fn decode(
+        &self,
+        abi_event: &Event,
+        event: &ChronicleEvent,
+    ) -> Result<Vec<DecodedParam>, anyhow::Error> {
+        let mut indexed = Vec::new();
+        let mut body = Vec::new();
+        for param in &abi_event.inputs {
+            let ty = param.resolve()?;
+            match param.indexed {
+                true => indexed.push(ty),
+                false => body.push(ty),
+            }
+        }
+
+        let decoded = decode_event(
+            event.topics.clone(),
+            event.data.clone(),
+            DynSolType::Tuple(body),
+            indexed,
+        )?;
+        self.abi.decoded_params(abi_event, decoded)
+    }
+}
+
+/// This function builds the log filter of an indexer, every list is a set of accepted values and an empty list accepts any value
+/// params:
+/// addresses: &[String] - The addresses of the contracts emitting the events
+/// selectors: &[B256] - The event signature hashes, matched against the first topic
+/// topics: [&[String]; 3] - The accepted values of the three indexed topics, an address is padded to a topic
+pub fn event_filter(
+    addresses: &[String],
+    selectors: &[B256],
+    topics: [&[String]; 3],
+) -> Result<Filter, anyhow::Error> {
+    let addresses = addresses
+        .iter()
+        .map(|address| {
+            address
+                .parse::<Address>()
+                .map_err(|e| anyhow!("Invalid address {address}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [topic1, topic2, topic3] = topics;
+
+    Ok(Filter::new()
+        .address(addresses)
+        .event_signature(selectors.to_vec())
+        .topic1(parse_topics(topic1)?)
+        .topic2(parse_topics(topic2)?)
+        .topic3(parse_topics(topic3)?))
+}
+
+/// This function parses topic values, accepting both 32 byte words and addresses
+fn parse_topics(topics: &[String]) -> Result<Vec<B256>, anyhow::Error> {
+    topics
+        .iter()
+        .map(|topic| {
+            topic
+                .parse::<B256>()
+                .or_else(|_| topic.parse::<Address>().map(Address::into_word))
+                .map_err(|e| anyhow!("Invalid topic {topic}: {e}"))
+        })
+        .collect()
 }
 
+/// This function indexes the events from `from_block` onwards and then stores new events as they arrive.
+/// The subscription is opened before the backfill, live logs are buffered while the history is queried,
+/// and the blocks between the backfill end and the first live log are filled so the indexed history is contiguous.
+/// returns an error once the subscription is closed, so the caller can reconnect
 pub async fn subscribe_to_events(
     provider: RootProvider<PubSubFrontend>,
-    addr: Vec<Address>,
-    event_sig: B256,
+    decoder: &LogDecoder,
+    filter: Filter,
+    from_block: u64,
+    backfill: &BackfillConfig,
     client: &mut tokio_postgres::Client,
     name: &str,
-) {
-    let filter = Filter::new()
-        .address(addr)
-        .event_signature(event_sig)
-        .from_block(BlockNumberOrTag::Latest);
-
+) -> Result<(), anyhow::Error> {
     let sub = provider
-        .subscribe_logs(&filter)
-        .await
-        .expect("Failed to subscribe to logs");
+        .subscribe_logs(&filter.clone().from_block(BlockNumberOrTag::Latest))
+        .await?;
     let mut stream = sub.into_stream();
+    let backfill_end = provider.get_block_number().await?;
+
+    // Live logs are buffered while the history up to the backfill end is queried
+    let mut buffered_logs = Vec::new();
+    if from_block <= backfill_end {
+        let backfill = backfill_events(
+            &provider,
+            decoder,
+            &filter,
+            from_block..=backfill_end,
+            backfill,
+            client,
+            name,
+        );
+        tokio::pin!(backfill);
+        loop {
+            tokio::select! {
+                result = &mut backfill => break result?,
+                Some(log) = stream.next() => buffered_logs.push(log),
+            }
+        }
+    } else if from_block > 0 {
+        store_checkpoint(client, name, from_block - 1).await?;
+    }
 
+    let mut indexer = LiveLogIndexer {
+        provider: &provider,
+        decoder,
+        filter: &filter,
+        backfill,
+        tracker: BlockHashTracker::new(
+            REORG_TRACKING_DEPTH,
+            get_recent_block_hashes(client, name, REORG_TRACKING_DEPTH).await?,
+        ),
+        client,
+        name,
+        current_block: None,
+        handoff_block: Some(backfill_end.max(from_block.saturating_sub(1))),
+    };
+    for log in buffered_logs {
+        indexer.index_log(log).await?;
+    }
     while let Some(log) = stream.next().await {
-        store_event_to_db(&log.into(), client, name)
-            .await
-            .expect("Failed to store event to db");
+        indexer.index_log(log).await?;
     }
+
+    bail!("The log subscription of indexer {name} was closed")
+}
+
+/// This stores live logs on top of the indexed history.
+/// Block hashes of recently indexed blocks are tracked, when a log is marked `removed` it is deleted,
+/// and when a log contradicts the tracked chain every orphaned block is dropped and re-indexed.
+struct LiveLogIndexer<'a> {
+    provider: &'a RootProvider<PubSubFrontend>,
+    decoder: &'a LogDecoder,
+    filter: &'a Filter,
+    /// This is how the blocks missed between two live logs, or replaced by a reorg, are fetched
+    backfill: &'a BackfillConfig,
+    client: &'a mut tokio_postgres::Client,
+    name: &'a str,
+    tracker: BlockHashTracker,
+    /// This is the block of the last stored live log
+    current_block: Option<u64>,
+    /// This is the last block indexed by the backfill, it is cleared once the first live log past it is stored
+    handoff_block: Option<u64>,
+}
+
+impl LiveLogIndexer<'_> {
+    async fn index_log(&mut self, log: Log) -> Result<(), anyhow::Error> {
+        let removed = log.removed;
+        let event = self.decoder.event(self.provider, log).await?;
+
+        if removed {
+            warn!(
+                "Indexer {} received removed log {}:{} from block {}",
+                self.name, event.transaction_hash, event.log_index, event.block_number
+            );
+            delete_event_from_db(&event, self.client, self.name).await?;
+            rollback_to_block(self.client, self.name, event.block_number).await?;
+            self.tracker.rollback(event.block_number);
+            self.current_block = None;
+            // The replacement logs of a rolled back block must not be skipped as already indexed
+            self.handoff_block = self
+                .handoff_block
+                .map(|handoff_block| handoff_block.min(event.block_number.saturating_sub(1)));
+            return Ok(());
+        }
+
+        if let Some(handoff_block) = self.handoff_block {
+            // Logs buffered during the backfill were already stored by it
+            if event.block_number <= handoff_block {
+                return Ok(());
+            }
+            self.handoff_block = None;
+
+            if event.block_number > handoff_block + 1 {
+                self.backfill_blocks(handoff_block + 1..=event.block_number - 1)
+                    .await?;
+            }
+        }
+
+        if self.tracker.is_reorg(event.block_number, event.block_hash) {
+            let fork_block = find_fork_block(self.provider, &self.tracker)
+                .await?
+                .map_or(event.block_number, |fork_block| {
+                    fork_block.min(event.block_number)
+                });
+            warn!(
+                "Indexer {} detected a reorg, re-indexing blocks {} to {}",
+                self.name, fork_block, event.block_number
+            );
+            rollback_to_block(self.client, self.name, fork_block).await?;
+            self.tracker.rollback(fork_block);
+
+            // The canonical logs of the reorged range, this includes the log that was just received
+            self.backfill_blocks(fork_block..=event.block_number)
+                .await?;
+            self.current_block = Some(event.block_number);
+            return Ok(());
+        }
+
+        // Logs arrive block by block, so the first log of a new block means every earlier block is done
+        if self
+            .current_block
+            .is_some_and(|block_number| event.block_number > block_number)
+        {
+            store_checkpoint(self.client, self.name, event.block_number - 1).await?;
+        }
+        self.current_block = Some(event.block_number);
+
+        store_event_to_db(&event, self.client, self.name).await?;
+        self.tracker.insert(event.block_number, event.block_hash);
+
+        Ok(())
+    }
+
+    /// This function indexes `blocks` in chunks like the initial backfill, checkpointing each chunk,
+    /// then tracks the block hashes of the events stored so far
+    async fn backfill_blocks(&mut self, blocks: RangeInclusive<u64>) -> Result<(), anyhow::Error> {
+        backfill_events(
+            self.provider,
+            self.decoder,
+            self.filter,
+            blocks,
+            self.backfill,
+            self.client,
+            self.name,
+        )
+        .await?;
+        self.tracker = BlockHashTracker::new(
+            REORG_TRACKING_DEPTH,
+            get_recent_block_hashes(self.client, self.name, REORG_TRACKING_DEPTH).await?,
+        );
+
+        Ok(())
+    }
+}
+
+/// This function follows the chain head and stores events once their block is deep enough for `finality`.
+/// Every new head backfills the blocks between the checkpoint and the new safe block,
+/// so the events stored can no longer be reorged away.
+/// returns an error once the subscription is closed, so the caller can reconnect
+pub async fn subscribe_to_finalized_events(
+    provider: RootProvider<PubSubFrontend>,
+    decoder: &LogDecoder,
+    filter: Filter,
+    finality: Finality,
+    backfill: BackfillConfig,
+    client: &mut tokio_postgres::Client,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let sub = provider.subscribe_blocks().await?;
+    let mut stream = sub.into_stream();
+    let mut last_indexed_block = match get_checkpoint(client, name).await? {
+        Some(block_number) => block_number,
+        None => finality.safe_block(&provider).await?,
+    };
+
+    while stream.next().await.is_some() {
+        let safe_block = finality.safe_block(&provider).await?;
+        if safe_block <= last_indexed_block {
+            continue;
+        }
+
+        backfill_events(
+            &provider,
+            decoder,
+            &filter,
+            last_indexed_block + 1..=safe_block,
+            &backfill,
+            client,
+            name,
+        )
+        .await?;
+        last_indexed_block = safe_block;
+    }
+
+    bail!("The block subscription of indexer {name} was closed")
 }
 
 /// This function is used to decode an event
@@ -85,32 +393,32 @@ pub mod tests {
     const DB_URL: &str = "host=localhost user=postgres";
     const NAME: &str = "events";
 
-    #[tokio::test]
-    #[ignore]
-    pub async fn test_query_events_works() {
-        let rpc_url = "wss://eth.merkle.io";
-
-        // Create the provider.
-        let ws = WsConnect::new(rpc_url);
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
-
-        let block_num = 19664198u64;
-        let uniswap_token_address = address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984");
-        let tranfer_event_signature =
-            b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
-
-        let events = query_events(
-            provider,
-            uniswap_token_address,
-            tranfer_event_signature,
-            block_num.into(),
+    #[test]
+    pub fn test_event_filter_from_lists() {
+        let filter = event_filter(
+            &[
+                "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string(),
+                "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".to_string(),
+            ],
+            &[
+                b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"),
+                b256!("8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"),
+            ],
+            [
+                &["0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string()],
+                &[],
+                &[],
+            ],
         )
-        .await
         .unwrap();
 
-        for log in events {
-            println!("Uniswap token logs: {log:?}");
-        }
+        assert_eq!(filter.address.iter().count(), 2);
+        assert_eq!(filter.topics[0].iter().count(), 2);
+        assert!(filter.topics[1]
+            .iter()
+            .eq([&address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984").into_word()]));
+        assert!(filter.topics[2].is_empty());
+        assert!(event_filter(&["0x1234".to_string()], &[], [&[], &[], &[]]).is_err());
     }
 
     #[tokio::test]
@@ -154,18 +462,25 @@ pub mod tests {
         let transfer_event_signature =
             b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
 
-        let mut client = create_db_instance(&DB_URL.into())
+        let mut client = create_db_instance(DB_URL)
             .await
             .expect("Could not create db instance");
+        let block_num = provider.get_block_number().await.unwrap();
+        let filter = Filter::new()
+            .address(vec![uniswap_token_address])
+            .event_signature(transfer_event_signature);
 
         subscribe_to_events(
             provider,
-            vec![uniswap_token_address],
-            transfer_event_signature,
+            &LogDecoder::new(1, Arc::default()),
+            filter,
+            block_num,
+            &BackfillConfig::default(),
             &mut client,
             NAME,
         )
-        .await;
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
@@ -0,0 +1,607 @@
+use std::collections::BTreeMap;
+
+use crate::IndexerConfig;
+use alloy::{
+    dyn_abi::{DecodedEvent, DynSolType, DynSolValue, Specifier},
+    json_abi::{Event, EventParam, JsonAbi},
+    primitives::{hex, B256},
+};
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+/// The columns every event table has, decoded parameters with these names are prefixed with their event name
+const EVENT_TABLE_COLUMNS: &[&str] = &[
+    "id",
+    "chain_id",
+    "address",
+    "block_number",
+    "block_hash",
+    "transaction_hash",
+    "log_index",
+    "topic0",
+    "topic1",
+    "topic2",
+    "topic3",
+    "data",
+    "block_timestamp",
+    "decoded",
+];
+
+/// This is the Postgres type a decoded event parameter is stored as
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Bytea,
+    /// This is only used by the event table columns, decoded integers are stored as `Numeric`
+    BigInt,
+    Numeric,
+    Boolean,
+    Text,
+    Jsonb,
+}
+
+impl ColumnType {
+    /// Maps a Solidity type to its column type, indexed parameters of reference types only keep their hash
+    pub fn from_param(ty: &DynSolType, indexed: bool) -> Self {
+        match ty {
+            DynSolType::Address | DynSolType::FixedBytes(_) | DynSolType::Function => Self::Bytea,
+            DynSolType::Int(_) | DynSolType::Uint(_) => Self::Numeric,
+            DynSolType::Bool => Self::Boolean,
+            _ if indexed => Self::Bytea,
+            DynSolType::Bytes => Self::Bytea,
+            DynSolType::String => Self::Text,
+            _ => Self::Jsonb,
+        }
+    }
+
+    /// Returns the Postgres type name
+    pub fn sql_type(&self) -> &'static str {
+        match self {
+            Self::Bytea => "BYTEA",
+            Self::BigInt => "BIGINT",
+            Self::Numeric => "NUMERIC",
+            Self::Boolean => "BOOLEAN",
+            Self::Text => "TEXT",
+            Self::Jsonb => "JSONB",
+        }
+    }
+
+    /// Returns the SQL expression reading the text parameter `$index` as this type
+    pub fn cast_param(&self, index: usize) -> String {
+        self.cast(&format!("${index}::TEXT"))
+    }
+
+    /// Returns the SQL expression reading the text expression `text` as this type
+    pub fn cast(&self, text: &str) -> String {
+        match self {
+            Self::Bytea => format!("decode({text}, 'hex')"),
+            _ => format!("{text}::{}", self.sql_type()),
+        }
+    }
+
+    /// Returns the text form of `value` read by `ColumnType::cast`, bytes lose their 0x prefix
+    pub fn to_sql_text(&self, value: &str) -> String {
+        match self {
+            Self::Bytea => value.trim_start_matches("0x").to_ascii_lowercase(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Returns the SQL expression selecting `column` as text, bytes are selected as 0x prefixed hex
+    pub fn select(&self, column: &str) -> String {
+        match self {
+            Self::Bytea => format!("'0x' || encode(\"{column}\", 'hex')"),
+            _ => format!("\"{column}\"::TEXT"),
+        }
+    }
+
+    /// Converts the text selected by `ColumnType::select` to JSON
+    pub fn json_from_text(&self, text: String) -> Result<serde_json::Value, anyhow::Error> {
+        Ok(match self {
+            Self::BigInt => text.parse::<i64>()?.into(),
+            Self::Boolean => text.parse::<bool>()?.into(),
+            Self::Jsonb => serde_json::from_str(&text)?,
+            Self::Bytea | Self::Numeric | Self::Text => text.into(),
+        })
+    }
+}
+
+/// This is the column of an event table holding a decoded event parameter
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecodedColumn {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+/// This is a decoded event parameter and the column it is stored in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DecodedParam {
+    /// This is the name of the parameter in the ABI
+    pub name: String,
+    pub column: DecodedColumn,
+    /// This is the value of the parameter, integers are decimal strings and bytes are 0x prefixed hex strings
+    pub value: serde_json::Value,
+}
+
+impl DecodedParam {
+    /// Returns the text form of the value bound to the column's `ColumnType::cast_param`
+    pub fn sql_value(&self) -> Option<String> {
+        let column_type = self.column.column_type;
+        match column_type {
+            ColumnType::Bytea | ColumnType::BigInt | ColumnType::Numeric | ColumnType::Text => self
+                .value
+                .as_str()
+                .map(|value| column_type.to_sql_text(value)),
+            ColumnType::Boolean => self.value.as_bool().map(|value| value.to_string()),
+            ColumnType::Jsonb => Some(self.value.to_string()),
+        }
+    }
+}
+
+/// This function collects decoded parameters into a JSON object keyed by parameter name,
+/// unnamed parameters are keyed by their column name
+/// returns None when there are no decoded parameters
+pub fn params_to_json(params: &[DecodedParam]) -> Option<serde_json::Value> {
+    if params.is_empty() {
+        return None;
+    }
+
+    let object = params
+        .iter()
+        .map(|param| {
+            let name = match param.name.is_empty() {
+                true => param.column.name.clone(),
+                false => param.name.clone(),
+            };
+            (name, param.value.clone())
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    Some(object.into())
+}
+
+/// This function converts a decoded value to JSON, integers are kept as decimal strings so no precision is lost
+pub fn value_to_json(value: &DynSolValue) -> serde_json::Value {
+    match value {
+        DynSolValue::Bool(value) => (*value).into(),
+        DynSolValue::Int(value, _) => value.to_string().into(),
+        DynSolValue::Uint(value, _) => value.to_string().into(),
+        DynSolValue::FixedBytes(word, size) => hex::encode_prefixed(&word[..*size]).into(),
+        DynSolValue::Address(address) => address.to_checksum(None).into(),
+        DynSolValue::Function(function) => hex::encode_prefixed(function).into(),
+        DynSolValue::Bytes(bytes) => hex::encode_prefixed(bytes).into(),
+        DynSolValue::String(value) => value.clone().into(),
+        DynSolValue::Array(values) => values.iter().map(value_to_json).collect(),
+        // Fixed arrays and tuples
+        value => value
+            .as_fixed_seq()
+            .map_or(serde_json::Value::Null, |values| {
+                values.iter().map(value_to_json).collect()
+            }),
+    }
+}
+
+/// This function turns a parameter name into a column name, e.g. `tokenId` becomes `token_id`
+fn to_column_name(name: &str) -> String {
+    let mut column = String::with_capacity(name.len());
+    let mut previous: Option<char> = None;
+    for c in name.trim_start_matches('_').chars() {
+        if c.is_ascii_uppercase() {
+            if previous
+                .is_some_and(|previous| previous.is_ascii_lowercase() || previous.is_ascii_digit())
+            {
+                column.push('_');
+            }
+            column.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            column.push(c);
+        } else {
+            column.push('_');
+        }
+        previous = Some(c);
+    }
+
+    column
+}
+
+/// These are the events of an indexer, with the parameter types of every event whose definition is known
+#[derive(Debug, Clone, Default)]
+pub struct IndexerAbi {
+    /// These are the first topics of every indexed event
+    pub selectors: Vec<B256>,
+    /// These are the definitions of the indexed events, keyed by their first topic
+    pub events: BTreeMap<B256, Event>,
+}
+
+impl IndexerAbi {
+    /// This function resolves the events of an indexer from its `event_signature`, `abi_path` and `event_names`
+    /// params:
+    /// config: &IndexerConfig - The config of the indexer
+    pub fn from_config(config: &IndexerConfig) -> Result<Self, anyhow::Error> {
+        let mut abi = Self::default();
+        for signature in &config.event_signature {
+            abi.add_signature(signature)?;
+        }
+        if let Some(abi_path) = &config.abi_path {
+            let json = std::fs::read_to_string(abi_path)
+                .map_err(|e| anyhow!("Could not read the ABI file {abi_path}: {e}"))?;
+            abi.add_json_abi(&json, &config.event_names)?;
+        }
+
+        if abi.selectors.is_empty() {
+            bail!(
+                "Indexer {} has no events, set `event_signature` or `abi_path`",
+                config.event_name
+            );
+        }
+
+        Ok(abi)
+    }
+
+    /// Adds an event from either its keccak hash or its Solidity signature,
+    /// e.g. `Transfer(address indexed from,address indexed to,uint256 value)`.
+    /// A signature must mark its indexed parameters, a type-only one such as `Transfer(address,address,uint256)`
+    /// does not tell which parameters are topics, so its events could not be decoded.
+    pub fn add_signature(&mut self, signature: &str) -> Result<(), anyhow::Error> {
+        if let Ok(selector) = signature.parse::<B256>() {
+            self.add_selector(selector);
+            return Ok(());
+        }
+
+        let event = Event::parse(signature)
+            .map_err(|e| anyhow!("Invalid event signature {signature}: {e}"))?;
+        if !event.inputs.is_empty() && !event.inputs.iter().any(|param| param.indexed) {
+            bail!(
+                "Event signature {signature} marks no parameter as indexed, mark them with `indexed`, \
+                 e.g. `Transfer(address indexed from,address indexed to,uint256 value)`, \
+                 or configure an event without indexed parameters by its hash or through `abi_path`"
+            );
+        }
+        self.add_event(event);
+
+        Ok(())
+    }
+
+    /// Adds the events named in `event_names` from a JSON ABI, or a contract artifact holding one,
+    /// every event of the ABI is added when no name is given
+    pub fn add_json_abi(
+        &mut self,
+        json: &str,
+        event_names: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if let Some(artifact_abi) = value.get_mut("abi") {
+            value = artifact_abi.take();
+        }
+        let json_abi: JsonAbi = serde_json::from_value(value)?;
+
+        if event_names.is_empty() {
+            json_abi
+                .events()
+                .cloned()
+                .for_each(|event| self.add_event(event));
+            return Ok(());
+        }
+        for event_name in event_names {
+            let events = json_abi
+                .event(event_name)
+                .ok_or_else(|| anyhow!("The ABI has no event named {event_name}"))?;
+            events
+                .iter()
+                .cloned()
+                .for_each(|event| self.add_event(event));
+        }
+
+        Ok(())
+    }
+
+    /// Adds an event with known parameter types
+    pub fn add_event(&mut self, event: Event) {
+        let selector = event.selector();
+        self.add_selector(selector);
+        self.events.insert(selector, event);
+    }
+
+    /// Returns the definition of the event whose first topic is `selector`
+    pub fn event(&self, selector: &B256) -> Option<&Event> {
+        self.events.get(selector)
+    }
+
+    /// Returns the columns holding the decoded parameters of `event`, in the order of its inputs.
+    /// A parameter is stored in the column named after it, unless that name is taken by an event table column
+    /// or by a parameter of another type, then the column name is prefixed with the event name.
+    pub fn event_columns(&self, event: &Event) -> Result<Vec<DecodedColumn>, anyhow::Error> {
+        event
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, param)| {
+                let (name, column_type) = param_column(param, index)?;
+                let is_taken = EVENT_TABLE_COLUMNS.contains(&name.as_str())
+                    || self.has_conflicting_param(&name, column_type)?;
+                let name = match is_taken {
+                    true => format!("{}_{name}", to_column_name(&event.name)),
+                    false => name,
+                };
+
+                Ok(DecodedColumn { name, column_type })
+            })
+            .collect()
+    }
+
+    /// Returns the columns holding the decoded parameters of every event
+    pub fn columns(&self) -> Result<Vec<DecodedColumn>, anyhow::Error> {
+        let mut columns: Vec<DecodedColumn> = Vec::new();
+        for event in self.events.values() {
+            for column in self.event_columns(event)? {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+
+        Ok(columns)
+    }
+
+    /// Pairs the values decoded from a log of `event` with the columns they are stored in
+    pub fn decoded_params(
+        &self,
+        event: &Event,
+        decoded: DecodedEvent,
+    ) -> Result<Vec<DecodedParam>, anyhow::Error> {
+        let mut indexed = decoded.indexed.into_iter();
+        let mut body = decoded.body.into_iter();
+
+        event
+            .inputs
+            .iter()
+            .zip(self.event_columns(event)?)
+            .map(|(param, column)| {
+                let value = match param.indexed {
+                    true => indexed.next(),
+                    false => body.next(),
+                }
+                .ok_or_else(|| {
+                    anyhow!("The {} log is missing parameter {}", event.name, param.name)
+                })?;
+
+                Ok(DecodedParam {
+                    name: param.name.clone(),
+                    column,
+                    value: value_to_json(&value),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns true when another event has a parameter named `name` that is stored as another type
+    fn has_conflicting_param(
+        &self,
+        name: &str,
+        column_type: ColumnType,
+    ) -> Result<bool, anyhow::Error> {
+        for event in self.events.values() {
+            for (index, param) in event.inputs.iter().enumerate() {
+                let (param_name, param_type) = param_column(param, index)?;
+                if param_name == name && param_type != column_type {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn add_selector(&mut self, selector: B256) {
+        if !self.selectors.contains(&selector) {
+            self.selectors.push(selector);
+        }
+    }
+}
+
+/// Returns the unprefixed column name and type of the event parameter at `index`
+fn param_column(param: &EventParam, index: usize) -> Result<(String, ColumnType), anyhow::Error> {
+    let ty = param.resolve().map_err(|e| {
+        anyhow!(
+            "Unsupported type {} of parameter {}: {e}",
+            param.ty,
+            param.name
+        )
+    })?;
+    let name = match to_column_name(&param.name) {
+        name if name.is_empty() => format!("param{index}"),
+        name => name,
+    };
+
+    Ok((name, ColumnType::from_param(&ty, param.indexed)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use alloy::primitives::{b256, Address, U256};
+
+    const TRANSFER_SELECTOR: B256 =
+        b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+
+    #[test]
+    pub fn test_signature_computes_selector() {
+        let mut abi = IndexerAbi::default();
+        abi.add_signature("Transfer(address indexed from,address indexed to,uint256 value)")
+            .unwrap();
+        abi.add_signature("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")
+            .unwrap();
+
+        assert_eq!(abi.selectors, vec![TRANSFER_SELECTOR]);
+        let event = abi.event(&TRANSFER_SELECTOR).unwrap();
+        assert_eq!(event.name, "Transfer");
+        assert_eq!(event.inputs[2].name, "value");
+        assert_eq!(event.inputs[2].ty, "uint256");
+        assert!(event.inputs[0].indexed);
+        assert!(abi.add_signature("Transfer(address").is_err());
+    }
+
+    #[test]
+    pub fn test_signature_must_mark_indexed_params() {
+        let mut abi = IndexerAbi::default();
+
+        let error = abi
+            .add_signature("Transfer(address,address,uint256)")
+            .unwrap_err();
+        assert!(error.to_string().contains("marks no parameter as indexed"));
+        assert!(abi.selectors.is_empty());
+        // An event without parameters has no topics to mark
+        abi.add_signature("Paused()").unwrap();
+    }
+
+    #[test]
+    pub fn test_json_abi_events_by_name() {
+        let json = r#"{"abi": [
+            {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+                {"name": "from", "type": "address", "indexed": true},
+                {"name": "to", "type": "address", "indexed": true},
+                {"name": "value", "type": "uint256", "indexed": false}
+            ]},
+            {"type": "event", "name": "Approval", "anonymous": false, "inputs": [
+                {"name": "owner", "type": "address", "indexed": true},
+                {"name": "spender", "type": "address", "indexed": true},
+                {"name": "value", "type": "uint256", "indexed": false}
+            ]}
+        ]}"#;
+
+        let mut abi = IndexerAbi::default();
+        abi.add_json_abi(json, &["Transfer".to_string()]).unwrap();
+        assert_eq!(abi.selectors, vec![TRANSFER_SELECTOR]);
+
+        let mut abi = IndexerAbi::default();
+        abi.add_json_abi(json, &[]).unwrap();
+        assert_eq!(abi.selectors.len(), 2);
+
+        assert!(IndexerAbi::default()
+            .add_json_abi(json, &["Swap".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    pub fn test_event_columns_are_typed_and_unique() {
+        let mut abi = IndexerAbi::default();
+        abi.add_signature("Transfer(address indexed from,address indexed to,uint256 value)")
+            .unwrap();
+        abi.add_signature(
+            "Memo(address indexed sender,string value,bytes data,uint8[] tokenIds,bool _isPublic)",
+        )
+        .unwrap();
+
+        let memo = abi
+            .events
+            .values()
+            .find(|event| event.name == "Memo")
+            .unwrap();
+        let columns = abi.event_columns(memo).unwrap();
+        let column = |name: &str, column_type| DecodedColumn {
+            name: name.to_string(),
+            column_type,
+        };
+
+        assert_eq!(
+            columns,
+            vec![
+                column("sender", ColumnType::Bytea),
+                column("memo_value", ColumnType::Text),
+                column("memo_data", ColumnType::Bytea),
+                column("token_ids", ColumnType::Jsonb),
+                column("is_public", ColumnType::Boolean),
+            ]
+        );
+        assert!(abi
+            .columns()
+            .unwrap()
+            .contains(&column("transfer_value", ColumnType::Numeric)));
+    }
+
+    #[test]
+    pub fn test_id_params_do_not_take_the_id_column() {
+        let mut abi = IndexerAbi::default();
+        abi.add_signature(
+            "TransferSingle(address indexed operator,address indexed from,address indexed to,uint256 id,uint256 value)",
+        )
+        .unwrap();
+        abi.add_signature("URI(string value,uint256 indexed _id)")
+            .unwrap();
+
+        let columns = abi.columns().unwrap();
+        assert!(columns.contains(&DecodedColumn {
+            name: "transfer_single_id".to_string(),
+            column_type: ColumnType::Numeric,
+        }));
+        assert!(columns.contains(&DecodedColumn {
+            name: "uri_id".to_string(),
+            column_type: ColumnType::Numeric,
+        }));
+        assert!(!columns.iter().any(|column| column.name == "id"));
+    }
+
+    #[test]
+    pub fn test_decoded_values_to_sql() {
+        let param = |value: DynSolValue, column_type| DecodedParam {
+            name: "value".to_string(),
+            column: DecodedColumn {
+                name: "value".to_string(),
+                column_type,
+            },
+            value: value_to_json(&value),
+        };
+
+        assert_eq!(
+            param(
+                DynSolValue::Uint(U256::from(1_000_000u64), 256),
+                ColumnType::Numeric
+            )
+            .sql_value(),
+            Some("1000000".to_string())
+        );
+        assert_eq!(
+            param(
+                DynSolValue::Address(Address::repeat_byte(0xab)),
+                ColumnType::Bytea
+            )
+            .sql_value(),
+            Some("ab".repeat(20))
+        );
+        assert_eq!(
+            param(DynSolValue::Bool(true), ColumnType::Boolean).sql_value(),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            param(
+                DynSolValue::Array(vec![DynSolValue::Uint(U256::from(1), 8)]),
+                ColumnType::Jsonb
+            )
+            .sql_value(),
+            Some(r#"["1"]"#.to_string())
+        );
+        assert_eq!(ColumnType::Numeric.cast_param(13), "$13::TEXT::NUMERIC");
+    }
+
+    #[test]
+    pub fn test_params_to_json_keys_by_name() {
+        let mut abi = IndexerAbi::default();
+        abi.add_signature("Deposit(address indexed,uint256 amount)")
+            .unwrap();
+        let event = abi.events.values().next().unwrap().clone();
+        let decoded = DecodedEvent {
+            selector: Some(event.selector()),
+            indexed: vec![DynSolValue::Address(Address::ZERO)],
+            body: vec![DynSolValue::Uint(U256::from(7), 256)],
+        };
+        let params = abi.decoded_params(&event, decoded).unwrap();
+
+        assert_eq!(
+            params_to_json(&params),
+            Some(serde_json::json!({
+                "param0": Address::ZERO.to_checksum(None),
+                "amount": "7",
+            }))
+        );
+        assert_eq!(params_to_json(&[]), None);
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::anyhow;
+use futures_util::{stream, StreamExt};
+use postgres::NoTls;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio_postgres::AsyncMessage;
+
+/// This is the channel stored and removed events are announced on, see the `notify listeners of stored and removed events` migration
+pub const EVENT_NOTIFICATION_CHANNEL: &str = "chronicle_events";
+
+/// This announces a change to the events of an indexer, the name of the indexer is also the name of its event table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventNotification {
+    /// Events were stored, they are the rows after the last one read
+    Stored { indexer: String },
+    /// The events of these blocks were removed or moved to other blocks, e.g. by a reorg
+    Removed {
+        indexer: String,
+        from_block: u64,
+        to_block: u64,
+    },
+}
+
+impl EventNotification {
+    /// Parses the `{table}:stored` and `{table}:removed:{from_block}:{to_block}` payloads sent by the event table triggers
+    pub fn parse(payload: &str) -> Result<Self, anyhow::Error> {
+        let invalid = || anyhow!("Invalid event notification {payload}");
+        let parts: Vec<&str> = payload.split(':').collect();
+
+        match parts.as_slice() {
+            [indexer, "stored"] => Ok(Self::Stored {
+                indexer: indexer.to_string(),
+            }),
+            [indexer, "removed", from_block, to_block] => Ok(Self::Removed {
+                indexer: indexer.to_string(),
+                from_block: from_block.parse().map_err(|_| invalid())?,
+                to_block: to_block.parse().map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns the name of the indexer whose events changed
+    pub fn indexer(&self) -> &str {
+        match self {
+            Self::Stored { indexer } | Self::Removed { indexer, .. } => indexer,
+        }
+    }
+}
+
+/// This function opens a connection listening for stored and removed events.
+/// The returned client must be kept alive, the receiver ends when the connection is closed.
+/// params:
+/// url: &str - The URL of the database
+pub async fn listen_for_events(
+    url: &str,
+) -> Result<(tokio_postgres::Client, UnboundedReceiver<EventNotification>), anyhow::Error> {
+    let (client, mut connection) = tokio_postgres::connect(url, NoTls).await?;
+    let (sender, receiver) = unbounded_channel();
+
+    // Notifications are only delivered while the connection is polled for its messages
+    tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    match EventNotification::parse(notification.payload()) {
+                        Ok(event) => {
+                            if sender.send(event).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => eprintln!("notification error: {}", e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("connection error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    client
+        .batch_execute(&format!("LISTEN {EVENT_NOTIFICATION_CHANNEL}"))
+        .await?;
+
+    Ok((client, receiver))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        db::{
+            checkpoint::rollback_to_block,
+            create_db_instance,
+            migrations::{run_migrations, MIGRATIONS_TABLE},
+            store_events_with_checkpoint,
+        },
+        indexer::ChronicleEvent,
+    };
+    use alloy::primitives::{address, b256, Bytes};
+    use std::time::Duration;
+
+    const DB_URL: &str = "host=localhost user=postgres";
+
+    #[test]
+    pub fn test_parse_event_notification() {
+        assert_eq!(
+            EventNotification::parse("uni_transfers:stored").unwrap(),
+            EventNotification::Stored {
+                indexer: "uni_transfers".to_string(),
+            }
+        );
+        assert_eq!(
+            EventNotification::parse("uni_transfers:removed:18000000:18000002").unwrap(),
+            EventNotification::Removed {
+                indexer: "uni_transfers".to_string(),
+                from_block: 18_000_000,
+                to_block: 18_000_002,
+            }
+        );
+        assert!(EventNotification::parse("uni_transfers").is_err());
+        assert!(EventNotification::parse("uni_transfers:42").is_err());
+        assert!(EventNotification::parse("uni_transfers:removed:latest:1").is_err());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    pub async fn test_changes_are_notified_once_per_statement() {
+        const NAME: &str = "notified_events";
+        let mut client = create_db_instance(DB_URL)
+            .await
+            .expect("Could not create db instance");
+        client
+            .batch_execute(&format!(
+                "
+                    DROP TABLE IF EXISTS {NAME};
+                    DELETE FROM {MIGRATIONS_TABLE} WHERE scope = 'events.{NAME}';
+                "
+            ))
+            .await
+            .unwrap();
+        run_migrations(&mut client, &[NAME]).await.unwrap();
+        let (_listener, mut notifications) = listen_for_events(DB_URL).await.unwrap();
+
+        let events: Vec<ChronicleEvent> = (0..3)
+            .map(|log_index| ChronicleEvent {
+                chain_id: 1,
+                address: address!("88da6bf26964af9d7eed9e03e53415d37aa96045"),
+                block_number: 5 + log_index,
+                block_hash: b256!(
+                    "000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045"
+                ),
+                transaction_hash: b256!(
+                    "000000000000000000000000d8da6bf26964af9d7eed9e03e53415d37aa96045"
+                ),
+                log_index,
+                topics: Vec::new(),
+                data: Bytes::from_static(&[0x69]),
+                block_timestamp: None,
+                decoded: Vec::new(),
+            })
+            .collect();
+        store_events_with_checkpoint(&events, 7, &mut client, NAME)
+            .await
+            .unwrap();
+        rollback_to_block(&mut client, NAME, 6).await.unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(Some(notification)) =
+            tokio::time::timeout(Duration::from_millis(500), notifications.recv()).await
+        {
+            if notification.indexer() == NAME {
+                received.push(notification);
+            }
+        }
+
+        // The three events are stored in one transaction, so they are announced once
+        assert_eq!(
+            received,
+            vec![
+                EventNotification::Stored {
+                    indexer: NAME.to_string(),
+                },
+                EventNotification::Removed {
+                    indexer: NAME.to_string(),
+                    from_block: 6,
+                    to_block: 7,
+                },
+            ]
+        );
+    }
+}
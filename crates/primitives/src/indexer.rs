@@ -2,27 +2,166 @@ use alloy::{
     primitives::{Address, Bytes, B256, U256},
     rpc::types::eth::{Log, Transaction},
 };
-use async_graphql::SimpleObject;
+use async_graphql::{connection::CursorType, InputObject, Json, SimpleObject};
 use serde::{Deserialize, Serialize};
 
-use crate::db::store_event_to_db;
+use crate::{abi::DecodedParam, db::store_event_to_db};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ChronicleEvent {
+    pub chain_id: u64,
     pub address: Address,
     pub block_number: u64,
+    pub block_hash: B256,
     pub transaction_hash: B256,
+    pub log_index: u64,
     pub topics: Vec<B256>,
     pub data: Bytes,
+    pub block_timestamp: Option<u64>,
+    /// These are the parameters decoded with the indexer ABI, empty when the event is not in the ABI
+    #[serde(default)]
+    pub decoded: Vec<DecodedParam>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, SimpleObject)]
 pub struct DisplayChronicleEvent {
+    pub chain_id: u64,
     pub address: String,
-    pub block_number: String,
+    pub block_number: u64,
+    pub block_hash: String,
     pub transaction_hash: String,
+    pub log_index: u64,
     pub topics: Vec<String>,
     pub data: String,
+    pub block_timestamp: Option<u64>,
+    /// These are the decoded parameters keyed by name, null when the indexer has no ABI for the event
+    pub decoded: Option<Json<serde_json::Value>>,
+}
+
+/// This selects events, every condition that is set must hold and a list matches any of its values
+#[derive(Serialize, Deserialize, Debug, Clone, Default, InputObject)]
+pub struct ChronicleEventFilter {
+    /// Only events of this chain
+    pub chain_id: Option<u64>,
+    /// Only events from this block onwards
+    pub from_block: Option<u64>,
+    /// Only events up to this block, inclusive
+    pub to_block: Option<u64>,
+    /// Only events emitted by these contracts
+    pub address: Option<Vec<String>>,
+    /// Only events from these blocks
+    pub block_hash: Option<Vec<String>>,
+    /// Only events emitted by these transactions
+    pub transaction_hash: Option<Vec<String>>,
+    pub topic0: Option<Vec<String>>,
+    pub topic1: Option<Vec<String>>,
+    pub topic2: Option<Vec<String>>,
+    pub topic3: Option<Vec<String>>,
+    /// Only events from blocks mined at or after this unix timestamp
+    pub from_timestamp: Option<u64>,
+    /// Only events from blocks mined at or before this unix timestamp
+    pub to_timestamp: Option<u64>,
+}
+
+impl ChronicleEventFilter {
+    /// Checks that every address, hash and topic of the filter is well formed,
+    /// so malformed values are rejected before the filter reaches `get_filtered_events`
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        for address in self.address.iter().flatten() {
+            address
+                .parse::<Address>()
+                .map_err(|e| anyhow::anyhow!("Invalid address {address}: {e}"))?;
+        }
+
+        let hashes = [
+            ("block_hash", &self.block_hash),
+            ("transaction_hash", &self.transaction_hash),
+            ("topic0", &self.topic0),
+            ("topic1", &self.topic1),
+            ("topic2", &self.topic2),
+            ("topic3", &self.topic3),
+        ];
+        for (field, hashes) in hashes {
+            for hash in hashes.iter().flatten() {
+                hash.parse::<B256>()
+                    .map_err(|e| anyhow::anyhow!("Invalid {field} {hash}: {e}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether `event` meets every condition of the filter, like the events selected by
+    /// `get_filtered_events`. Addresses and topics are compared ignoring case.
+    pub fn matches(&self, event: &DisplayChronicleEvent) -> bool {
+        let in_range = |value: Option<u64>, from: Option<u64>, to: Option<u64>| match value {
+            Some(value) => {
+                from.into_iter().all(|from| value >= from) && to.into_iter().all(|to| value <= to)
+            }
+            None => from.is_none() && to.is_none(),
+        };
+        let any_of = |values: &Option<Vec<String>>, value: Option<&String>| match values {
+            Some(values) => value.is_some_and(|value| {
+                values
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(value))
+            }),
+            None => true,
+        };
+
+        self.chain_id
+            .into_iter()
+            .all(|chain_id| chain_id == event.chain_id)
+            && in_range(Some(event.block_number), self.from_block, self.to_block)
+            && in_range(
+                event.block_timestamp,
+                self.from_timestamp,
+                self.to_timestamp,
+            )
+            && any_of(&self.address, Some(&event.address))
+            && any_of(&self.block_hash, Some(&event.block_hash))
+            && any_of(&self.transaction_hash, Some(&event.transaction_hash))
+            && [&self.topic0, &self.topic1, &self.topic2, &self.topic3]
+                .into_iter()
+                .enumerate()
+                .all(|(index, topics)| any_of(topics, event.topics.get(index)))
+    }
+}
+
+/// This is the position of an event in block and log index order, it is used as a pagination cursor
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventCursor {
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+impl EventCursor {
+    /// Returns the cursor of an event
+    pub fn of(event: &DisplayChronicleEvent) -> Self {
+        Self {
+            block_number: event.block_number,
+            log_index: event.log_index,
+        }
+    }
+}
+
+impl CursorType for EventCursor {
+    type Error = anyhow::Error;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        let (block_number, log_index) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Invalid cursor {s}"))?;
+
+        Ok(Self {
+            block_number: block_number.parse()?,
+            log_index: log_index.parse()?,
+        })
+    }
+
+    fn encode_cursor(&self) -> String {
+        format!("{}:{}", self.block_number, self.log_index)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -51,7 +190,7 @@ impl From<Transaction> for ChronicleTransaction {
         Self {
             hash: tx.hash,
             nonce: tx.nonce,
-            block_hash: tx.block_hash.unwrap_or(B256::default()),
+            block_hash: tx.block_hash.unwrap_or_default(),
             block_number: tx.block_number.unwrap_or(0),
             from: tx.from,
             to: tx.to.unwrap_or(Address::ZERO),
@@ -67,34 +206,27 @@ impl From<Transaction> for ChronicleTransaction {
 impl From<Log> for ChronicleEvent {
     fn from(log: Log) -> Self {
         Self {
+            chain_id: 0,
             address: log.address(),
             block_number: log.block_number.unwrap_or(0),
+            block_hash: log.block_hash.unwrap_or_default(),
             transaction_hash: log.transaction_hash.unwrap_or(B256::default()),
+            log_index: log.log_index.unwrap_or(0),
             topics: log.data().clone().topics().to_vec(),
             data: log.inner.data.data,
+            block_timestamp: log.block_timestamp,
+            decoded: Vec::new(),
         }
     }
 }
 
-impl DisplayChronicleEvent {
-    pub fn new(
-        address: String,
-        block_number: String,
-        transaction_hash: String,
-        topics: Vec<String>,
-        data: String,
-    ) -> Self {
-        Self {
-            address,
-            block_number,
-            transaction_hash,
-            topics,
-            data,
-        }
+impl ChronicleEvent {
+    /// Sets the id of the chain the event was emitted on, logs do not carry it themselves
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
     }
-}
 
-impl ChronicleEvent {
     pub async fn store_event(
         &self,
         db_client: &mut tokio_postgres::Client,
@@ -105,3 +237,98 @@ impl ChronicleEvent {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_event_filter_matches_event() {
+        let event = DisplayChronicleEvent {
+            chain_id: 1,
+            address: "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string(),
+            block_number: 100,
+            topics: vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+            ],
+            block_timestamp: Some(1_714_000_000),
+            ..Default::default()
+        };
+        let filter = |filter: ChronicleEventFilter| filter.matches(&event);
+
+        assert!(filter(ChronicleEventFilter::default()));
+        assert!(filter(ChronicleEventFilter {
+            chain_id: Some(1),
+            from_block: Some(100),
+            address: Some(vec![
+                "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984".to_string()
+            ]),
+            topic0: Some(vec![
+                "0xDDF252AD1BE2C89B69C2B068FC378DAA952BA7F163C4A11628F55A4DF523B3EF".to_string()
+            ]),
+            to_timestamp: Some(1_714_000_000),
+            ..Default::default()
+        }));
+        assert!(!filter(ChronicleEventFilter {
+            to_block: Some(99),
+            ..Default::default()
+        }));
+        assert!(!filter(ChronicleEventFilter {
+            chain_id: Some(10),
+            ..Default::default()
+        }));
+        assert!(!filter(ChronicleEventFilter {
+            address: Some(vec![]),
+            ..Default::default()
+        }));
+        assert!(!filter(ChronicleEventFilter {
+            topic1: Some(vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string()
+            ]),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    pub fn test_event_filter_rejects_malformed_values() {
+        let filter = ChronicleEventFilter {
+            address: Some(vec![
+                "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string()
+            ]),
+            topic0: Some(vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+            ]),
+            ..Default::default()
+        };
+        assert!(filter.validate().is_ok());
+
+        let error = ChronicleEventFilter {
+            topic2: Some(vec!["0x1234' OR 1=1 --".to_string()]),
+            ..filter.clone()
+        }
+        .validate()
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid topic2 0x1234' OR 1=1 --"));
+        assert!(ChronicleEventFilter {
+            address: Some(vec!["0x1234".to_string()]),
+            ..filter
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_event_cursor_round_trip() {
+        let cursor = EventCursor {
+            block_number: 19_711_184,
+            log_index: 7,
+        };
+
+        assert_eq!(cursor.encode_cursor(), "19711184:7");
+        assert_eq!(EventCursor::decode_cursor("19711184:7").unwrap(), cursor);
+        assert!(EventCursor::decode_cursor("19711184").is_err());
+        assert!(EventCursor::decode_cursor("a:b").is_err());
+    }
+}
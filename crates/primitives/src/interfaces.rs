@@ -5,28 +5,26 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait ChronicleEventIndexer {
     type SubProvider;
-    type ContractAddress;
-    type EventSignature;
+    /// This selects the events to index, e.g. a set of contract addresses, event signatures and topics
+    type EventFilter;
     type BlockNumber;
 
     /// This function queries events from a specified block number
-    /// `[Filter]`, having `address`, `last_block` and `event_signature` as parameters
+    /// `[Filter]`, having the `filter` and `last_block` as parameters
     async fn query_events(
         &self,
         provider: Self::SubProvider,
-        addr: Self::ContractAddress,
-        event_sig: Self::EventSignature,
+        filter: Self::EventFilter,
         block_nuber: Self::BlockNumber,
         db_client: &mut tokio_postgres::Client,
     ) -> Result<(), anyhow::Error>;
 
-    /// This creates a filter and subscribes to an event returning the event
+    /// This subscribes to the events matching the filter
     /// stream <T: Stream<Item = Resp> + Unpin>
     async fn subscribe_to_events(
         &self,
         provider: Self::SubProvider,
-        addr: Vec<Self::ContractAddress>,
-        event_sig: Self::EventSignature,
+        filter: Self::EventFilter,
         db_client: &mut tokio_postgres::Client,
     ) -> Result<(), anyhow::Error>;
 }
@@ -1,8 +1,9 @@
+pub mod abi;
 pub mod db;
 pub mod errors;
 pub mod indexer;
 pub mod interfaces;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum StateMachine {
@@ -26,6 +27,15 @@ pub struct ServerConfig {
     pub db_url: String,
     /// This is the URL of the server
     pub server_url: String,
+    /// This is the largest number of db connections the server keeps open
+    #[serde(default)]
+    pub db_pool_size: Option<usize>,
+    /// This is the time in milliseconds a request waits for a free db connection before failing
+    #[serde(default)]
+    pub db_pool_timeout_ms: Option<u64>,
+    /// This is the time in milliseconds given to open a db connection
+    #[serde(default)]
+    pub db_connect_timeout_ms: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -34,16 +44,55 @@ pub struct IndexerConfig {
     pub event_name: String,
     /// This is represents the statemachaine to be indexed
     pub state_machine: String,
-    /// This is the RPC url of the state machine
+    /// This is the RPC url of the state machine, `ws://` and `wss://` urls and ipc socket paths are subscribed to
+    /// while `http://` and `https://` urls are polled
     pub rpc_url: String,
-    /// This is the address of the contract that is to be indexed
-    pub address: String,
-    /// This is the event signature of the event that is to be indexed
-    pub event_signature: String,
+    /// These are the addresses of the contracts that are to be indexed, a single address or a non-empty list
+    #[serde(deserialize_with = "one_or_more")]
+    pub address: Vec<String>,
+    /// These are the events that are to be indexed, a single event or a list.
+    /// Each is either an event signature hash or a Solidity signature, e.g. `Transfer(address indexed from,address indexed to,uint256 value)`
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub event_signature: Vec<String>,
+    /// This is the path of a contract JSON ABI, its events named in `event_names` are indexed
+    #[serde(default)]
+    pub abi_path: Option<String>,
+    /// These are the names of the events indexed from `abi_path`, every event of the ABI is indexed when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub event_names: Vec<String>,
+    /// These are the accepted values of the first indexed topic, any value matches when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub topic1: Vec<String>,
+    /// These are the accepted values of the second indexed topic, any value matches when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub topic2: Vec<String>,
+    /// These are the accepted values of the third indexed topic, any value matches when empty
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub topic3: Vec<String>,
     /// This is the block number to start indexing from
     pub block_number: u64,
     /// This is the URL of the database
     pub db_url: String,
+    /// This is the number of blocks an event must be buried under before it is stored, 0 stores events right away
+    #[serde(default)]
+    pub confirmations: u64,
+    /// This is the block tag (`safe` or `finalized`) events must be at or below before they are stored,
+    /// it takes precedence over `confirmations`; `latest` counts `confirmations` from the chain head
+    #[serde(default)]
+    pub finality_tag: Option<String>,
+    /// This is the largest number of blocks fetched by a single `eth_getLogs` call while backfilling,
+    /// it shrinks automatically when the provider rejects a range
+    #[serde(default)]
+    pub backfill_chunk_size: Option<u64>,
+    /// This is the number of block chunks fetched concurrently while backfilling
+    #[serde(default)]
+    pub backfill_workers: Option<usize>,
+    /// This is the largest number of `eth_getLogs` calls each backfill worker makes per second
+    #[serde(default)]
+    pub backfill_requests_per_second: Option<u32>,
+    /// This is the time in milliseconds between two polls of an HTTP rpc url
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
 }
 
 impl From<String> for StateMachine {
@@ -56,3 +105,78 @@ impl From<String> for StateMachine {
         }
     }
 }
+
+/// This deserializes a config value that is either a single string or a list of strings
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// This deserializes a config value that is either a single string or a non-empty list of strings
+fn one_or_more<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values = one_or_many(deserializer)?;
+    if values.is_empty() {
+        return Err(serde::de::Error::invalid_length(0, &"at least one value"));
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn indexer_config(address: serde_json::Value) -> IndexerConfig {
+        try_indexer_config(address).unwrap()
+    }
+
+    fn try_indexer_config(address: serde_json::Value) -> Result<IndexerConfig, serde_json::Error> {
+        serde_json::from_value(serde_json::json!({
+            "event_name": "events",
+            "state_machine": "EVM",
+            "rpc_url": "wss://eth.merkle.io",
+            "address": address,
+            "event_signature": "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+            "block_number": 0,
+            "db_url": "host=localhost user=postgres",
+        }))
+    }
+
+    #[test]
+    pub fn test_indexer_config_accepts_one_or_many_values() {
+        let config = indexer_config("0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".into());
+        assert_eq!(
+            config.address,
+            vec!["0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984"]
+        );
+        assert_eq!(config.event_signature.len(), 1);
+        assert!(config.topic1.is_empty());
+
+        let config = indexer_config(serde_json::json!([
+            "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984",
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+        ]));
+        assert_eq!(config.address.len(), 2);
+    }
+
+    #[test]
+    pub fn test_indexer_config_rejects_an_empty_address_list() {
+        let error = try_indexer_config(serde_json::json!([])).unwrap_err();
+        assert!(error.to_string().contains("at least one value"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::{self, Display};
+
+use async_graphql::{ErrorExtensions, Value};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// These are the errors the resolvers and routes return to clients, each is tagged with a `code`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerError {
+    /// The queried indexer is not configured
+    UnknownIndexer(String),
+    /// A request argument could not be used, e.g. a malformed cursor
+    InvalidArgument(String),
+    /// No db connection could be taken from the pool
+    DbUnavailable,
+    /// The db could not run a query
+    DbQueryFailed,
+}
+
+impl ServerError {
+    /// Returns the `code` of the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownIndexer(_) => "UNKNOWN_INDEXER",
+            Self::InvalidArgument(_) => "INVALID_ARGUMENT",
+            Self::DbUnavailable => "DB_UNAVAILABLE",
+            Self::DbQueryFailed => "DB_QUERY_FAILED",
+        }
+    }
+
+    /// Returns the HTTP status the REST routes answer the error with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::UnknownIndexer(_) => StatusCode::NOT_FOUND,
+            Self::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+            Self::DbUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::DbQueryFailed => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Returns the `INVALID_ARGUMENT` error of a request argument that could not be used
+    pub fn invalid_argument(error: impl Display) -> Self {
+        Self::InvalidArgument(error.to_string())
+    }
+
+    /// This function logs why no db connection could be taken and returns the `DB_UNAVAILABLE` error,
+    /// the cause is only logged so db details are not leaked to clients
+    /// params:
+    /// error: impl Display - The error of the pool
+    pub fn db_unavailable(error: impl Display) -> Self {
+        tracing::error!("Could not connect to the db: {}", error);
+        Self::DbUnavailable
+    }
+
+    /// This function logs why a query failed and returns the `DB_QUERY_FAILED` error
+    /// params:
+    /// error: impl Display - The error of the query
+    pub fn db_query_failed(error: impl Display) -> Self {
+        tracing::error!("Could not get events from db: {}", error);
+        Self::DbQueryFailed
+    }
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownIndexer(name) => write!(f, "Unknown indexer {name}"),
+            Self::InvalidArgument(reason) => write!(f, "Invalid argument: {reason}"),
+            Self::DbUnavailable => write!(f, "The db is unavailable"),
+            Self::DbQueryFailed => write!(f, "Could not get events from the db"),
+        }
+    }
+}
+
+impl ErrorExtensions for ServerError {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string())
+            .extend_with(|_, extensions| extensions.set("code", Value::from(self.code())))
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({
+            "error": {
+                "code": self.code(),
+                "message": self.to_string(),
+            }
+        });
+
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_server_errors_carry_their_code() {
+        let error = ServerError::UnknownIndexer("lab9".to_string()).extend();
+        assert_eq!(error.message, "Unknown indexer lab9");
+        assert_eq!(
+            error.extensions.unwrap().get("code"),
+            Some(&Value::from("UNKNOWN_INDEXER"))
+        );
+
+        let error = ServerError::db_unavailable("connection refused");
+        assert_eq!(error, ServerError::DbUnavailable);
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            error.extend().extensions.unwrap().get("code"),
+            Some(&Value::from("DB_UNAVAILABLE"))
+        );
+    }
+}
@@ -1,34 +1,86 @@
-use async_graphql::{http::GraphiQLSource, EmptyMutation, EmptySubscription, ObjectType, Schema};
-use async_graphql_axum::GraphQL;
+use async_graphql::{http::GraphiQLSource, EmptyMutation, ObjectType, Schema, SubscriptionType};
+use async_graphql_axum::{GraphQL, GraphQLSubscription};
 use axum::{
     response::{self, IntoResponse},
     routing::get,
     Router,
 };
 use chronicle_primitives::ServerConfig;
+use pool::create_db_pool;
+use registry::IndexerRegistry;
+use rest::{rest_routes, RestState};
+use rpc::{rpc_routes, RpcState};
+use subscription::EventBroadcast;
 use tokio::net::TcpListener;
+use typed::{build_typed_schema, TypedIndexer};
+pub mod errors;
+pub mod pool;
 pub mod query;
+pub mod registry;
+pub mod rest;
+pub mod rpc;
+pub mod subscription;
+pub mod typed;
 
 /// This function is used to serve the graphQL server and GraphiQL IDE.
 async fn graphiql() -> impl IntoResponse {
-    response::Html(GraphiQLSource::build().endpoint("/").finish())
+    response::Html(
+        GraphiQLSource::build()
+            .endpoint("/")
+            .subscription_endpoint("/ws")
+            .finish(),
+    )
+}
+
+/// This function is used to serve the GraphiQL IDE of the typed schema.
+async fn typed_graphiql() -> impl IntoResponse {
+    response::Html(GraphiQLSource::build().endpoint("/typed").finish())
 }
 
 /// This function is used to run the chronicle server.
 /// `[DB]` This is a generic type, which is used to store the database.
 /// `[Query]` This is a gaint Query entity, for all the Events enitities and all the tx enitities.
-pub async fn run_chronicle_server<Query>(
+/// `[Subscription]` This streams the events stored by the indexers, it is served over graphql-ws at `/ws`.
+/// `[indexers]` These are the indexers whose ABI events are served with their own types at `/typed`.
+pub async fn run_chronicle_server<Query, Subscription>(
     config: ServerConfig,
     query: Query,
+    subscription: Subscription,
+    indexers: Vec<TypedIndexer>,
 ) -> Result<(), anyhow::Error>
 where
     Query: ObjectType + 'static,
+    Subscription: SubscriptionType + 'static,
 {
     let url = config.server_url.clone();
-    let schema = Schema::build(query, EmptyMutation, EmptySubscription)
-        .data(config)
+    // Only the tables of the configured indexers can be queried
+    let registry = IndexerRegistry::new(indexers.iter().map(|indexer| indexer.name.clone()))?;
+    // Every request borrows its db connection from this pool instead of opening a new one
+    let pool = create_db_pool(&config)?;
+    let typed_schema = build_typed_schema(pool.clone(), &indexers)?;
+    let events = EventBroadcast::spawn(config.db_url.clone(), pool.clone(), registry.clone());
+    let rest = rest_routes(RestState {
+        pool: pool.clone(),
+        registry: registry.clone(),
+    });
+    let rpc = rpc_routes(RpcState::new(pool.clone(), registry.clone()));
+    let schema = Schema::build(query, EmptyMutation, subscription)
+        .data(pool)
+        .data(registry)
+        .data(events)
         .finish();
-    let app = Router::new().route("/", get(graphiql).post_service(GraphQL::new(schema)));
+    let app = Router::new()
+        .route(
+            "/",
+            get(graphiql).post_service(GraphQL::new(schema.clone())),
+        )
+        .route_service("/ws", GraphQLSubscription::new(schema))
+        .route(
+            "/typed",
+            get(typed_graphiql).post_service(GraphQL::new(typed_schema)),
+        )
+        .merge(rest)
+        .merge(rpc);
 
     tracing::info!(url);
     axum::serve(TcpListener::bind(url).await.unwrap(), app)
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use chronicle_primitives::ServerConfig;
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use tokio_postgres::NoTls;
+
+/// This is the number of db connections kept by the server when `db_pool_size` is not set
+pub const DEFAULT_POOL_SIZE: usize = 16;
+
+/// This is the time in milliseconds a request waits for a db connection when `db_pool_timeout_ms` is not set
+pub const DEFAULT_POOL_TIMEOUT_MS: u64 = 5_000;
+
+/// This is the time in milliseconds given to open a db connection when `db_connect_timeout_ms` is not set
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 5_000;
+
+/// This function creates the pool of db connections shared by the resolvers of the server.
+/// Connections are opened lazily, up to `db_pool_size`, and reused across requests.
+/// params:
+/// config: &ServerConfig - The server config, holding the db url and the pool size and timeouts
+pub fn create_db_pool(config: &ServerConfig) -> Result<Pool, anyhow::Error> {
+    let manager = Manager::from_config(
+        config.db_url.parse()?,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        },
+    );
+    let connect_timeout = Duration::from_millis(
+        config
+            .db_connect_timeout_ms
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS),
+    );
+
+    let pool = Pool::builder(manager)
+        .max_size(config.db_pool_size.unwrap_or(DEFAULT_POOL_SIZE))
+        .wait_timeout(Some(Duration::from_millis(
+            config.db_pool_timeout_ms.unwrap_or(DEFAULT_POOL_TIMEOUT_MS),
+        )))
+        .create_timeout(Some(connect_timeout))
+        .recycle_timeout(Some(connect_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()?;
+
+    Ok(pool)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn server_config(db_url: &str, db_pool_size: Option<usize>) -> ServerConfig {
+        ServerConfig {
+            db_url: db_url.to_string(),
+            server_url: "127.0.0.1:8010".to_string(),
+            db_pool_size,
+            db_pool_timeout_ms: None,
+            db_connect_timeout_ms: None,
+        }
+    }
+
+    #[test]
+    pub fn test_db_pool_uses_configured_size() {
+        let pool = create_db_pool(&server_config("host=localhost user=postgres", None)).unwrap();
+        assert_eq!(pool.status().max_size, DEFAULT_POOL_SIZE);
+        assert_eq!(pool.status().size, 0);
+
+        let pool =
+            create_db_pool(&server_config("postgresql://postgres@localhost", Some(4))).unwrap();
+        assert_eq!(pool.status().max_size, 4);
+
+        assert!(create_db_pool(&server_config("host=localhost port=not_a_port", None)).is_err());
+    }
+}
@@ -1,9 +1,29 @@
-use async_graphql::{Context, Object};
+use std::future::Future;
+
+use alloy::primitives::B256;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    Context, Object, ResultExt,
+};
 use chronicle_primitives::{
-    db::{create_db_instance, get_all_events, get_events_by_block_number, get_events_by_tx_hash},
-    indexer::DisplayChronicleEvent,
-    ServerConfig,
+    db::{
+        get_all_events, get_events_by_block_number, get_events_by_tx_hash, get_filtered_events,
+        EventPage,
+    },
+    indexer::{ChronicleEventFilter, DisplayChronicleEvent, EventCursor},
 };
+use deadpool_postgres::Pool;
+
+use crate::{errors::ServerError, registry::IndexerRegistry};
+
+/// This is the number of events in a page when `first` is not given
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// This is the largest number of events in a page, larger `first` values are lowered to it
+pub const MAX_PAGE_SIZE: usize = 1_000;
+
+/// This is a page of events in block and log index order
+pub type EventConnection = Connection<EventCursor, DisplayChronicleEvent>;
 
 pub struct ChronicleQuery;
 
@@ -13,16 +33,61 @@ impl ChronicleQuery {
         &self,
         cxt: &Context<'a>,
         name: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let config = cxt.data_unchecked::<ServerConfig>();
-        let mut db_client = create_db_instance(&config.db_url)
-            .await
-            .expect("Could not connect to the db");
-        let events = get_all_events(&mut db_client, &name)
-            .await
-            .expect("Could not get events from db");
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<EventConnection> {
+        let pool = cxt.data_unchecked::<Pool>();
+        let table = cxt
+            .data_unchecked::<IndexerRegistry>()
+            .table(&name)
+            .extend()?;
 
-        events
+        event_connection(first, after, |page| async move {
+            let mut db_client = pool
+                .get()
+                .await
+                .map_err(ServerError::db_unavailable)
+                .extend()?;
+            get_all_events(&mut db_client, table, page)
+                .await
+                .map_err(ServerError::db_query_failed)
+                .extend()
+        })
+        .await
+    }
+
+    /// Lists the events of an indexer matching every condition of `where`
+    async fn events<'a>(
+        &self,
+        cxt: &Context<'a>,
+        name: String,
+        #[graphql(name = "where")] filter: Option<ChronicleEventFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<EventConnection> {
+        let pool = cxt.data_unchecked::<Pool>();
+        let table = cxt
+            .data_unchecked::<IndexerRegistry>()
+            .table(&name)
+            .extend()?;
+        let filter = filter.unwrap_or_default();
+        filter
+            .validate()
+            .map_err(ServerError::invalid_argument)
+            .extend()?;
+
+        event_connection(first, after, |page| async move {
+            let mut db_client = pool
+                .get()
+                .await
+                .map_err(ServerError::db_unavailable)
+                .extend()?;
+            get_filtered_events(&mut db_client, table, &filter, page)
+                .await
+                .map_err(ServerError::db_query_failed)
+                .extend()
+        })
+        .await
     }
 
     async fn get_events_by_tx_hash<'a>(
@@ -30,32 +95,190 @@ impl ChronicleQuery {
         cxt: &Context<'a>,
         name: String,
         transaction_hash: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let config = cxt.data_unchecked::<ServerConfig>();
-        let mut db_client = create_db_instance(&config.db_url)
-            .await
-            .expect("Could not connect to the db");
-        let events = get_events_by_tx_hash(&mut db_client, &name, transaction_hash)
-            .await
-            .expect("Could not get events from db");
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<EventConnection> {
+        let pool = cxt.data_unchecked::<Pool>();
+        let table = cxt
+            .data_unchecked::<IndexerRegistry>()
+            .table(&name)
+            .extend()?;
+        let transaction_hash: B256 = transaction_hash
+            .parse()
+            .map_err(|e| {
+                ServerError::invalid_argument(format!(
+                    "Invalid transaction_hash {transaction_hash}: {e}"
+                ))
+            })
+            .extend()?;
 
-        events
+        event_connection(first, after, |page| async move {
+            let mut db_client = pool
+                .get()
+                .await
+                .map_err(ServerError::db_unavailable)
+                .extend()?;
+            get_events_by_tx_hash(&mut db_client, table, transaction_hash, page)
+                .await
+                .map_err(ServerError::db_query_failed)
+                .extend()
+        })
+        .await
     }
 
     async fn get_events_by_block_number<'a>(
         &self,
         cxt: &Context<'a>,
         name: String,
-        block_number: String,
-    ) -> Vec<DisplayChronicleEvent> {
-        let config = cxt.data_unchecked::<ServerConfig>();
-        let mut db_client = create_db_instance(&config.db_url)
+        block_number: u64,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<EventConnection> {
+        let pool = cxt.data_unchecked::<Pool>();
+        let table = cxt
+            .data_unchecked::<IndexerRegistry>()
+            .table(&name)
+            .extend()?;
+
+        event_connection(first, after, |page| async move {
+            let mut db_client = pool
+                .get()
+                .await
+                .map_err(ServerError::db_unavailable)
+                .extend()?;
+            get_events_by_block_number(&mut db_client, table, block_number, page)
+                .await
+                .map_err(ServerError::db_query_failed)
+                .extend()
+        })
+        .await
+    }
+}
+
+/// This function pages through events with the Relay `first` and `after` arguments.
+/// One event more than the page size is fetched, so the next page is only reported when it has events.
+/// params:
+/// first: Option<i32> - The number of events in the page, up to `MAX_PAGE_SIZE`
+/// after: Option<String> - The cursor of the last event of the previous page
+/// fetch: F - Fetches a page of events, its errors are returned as they are
+///
+/// returns an `INVALID_ARGUMENT` error, before fetching anything, when `after` is malformed or `first` is negative
+async fn event_connection<F, R>(
+    first: Option<i32>,
+    after: Option<String>,
+    fetch: F,
+) -> async_graphql::Result<EventConnection>
+where
+    F: FnOnce(EventPage) -> R,
+    R: Future<Output = async_graphql::Result<Vec<DisplayChronicleEvent>>>,
+{
+    let after = after
+        .map(|after| EventCursor::decode_cursor(&after))
+        .transpose()
+        .map_err(|e| ServerError::invalid_argument(format!("Invalid cursor: {e}")))
+        .extend()?;
+    let page_size = match first {
+        Some(first) => usize::try_from(first)
+            .map_err(|_| ServerError::invalid_argument("first must not be negative"))
+            .extend()?,
+        None => DEFAULT_PAGE_SIZE,
+    }
+    .min(MAX_PAGE_SIZE);
+
+    let mut events = fetch(EventPage {
+        after,
+        limit: page_size as u64 + 1,
+    })
+    .await?;
+    let has_next_page = events.len() > page_size;
+    events.truncate(page_size);
+
+    let mut connection = EventConnection::new(after.is_some(), has_next_page);
+    connection.edges.extend(
+        events
+            .into_iter()
+            .map(|event| Edge::new(EventCursor::of(&event), event)),
+    );
+
+    Ok(connection)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pool::create_db_pool;
+    use async_graphql::{EmptyMutation, EmptySubscription, Schema, Value};
+    use chronicle_primitives::ServerConfig;
+
+    fn error_code(error: &async_graphql::Error) -> Option<&Value> {
+        error
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("code"))
+    }
+
+    #[tokio::test]
+    pub async fn test_malformed_page_arguments_are_rejected_before_fetching() {
+        for (first, after) in [
+            (None, Some("not a cursor".to_string())),
+            (None, Some("5:-1".to_string())),
+            (Some(-1), None),
+        ] {
+            let Err(error) = event_connection(first, after, |_| async {
+                panic!("events were fetched");
+            })
             .await
-            .expect("Could not connect to the db");
-        let events = get_events_by_block_number(&mut db_client, &name, block_number)
+            else {
+                panic!("a page was returned");
+            };
+
+            assert_eq!(
+                error_code(&error),
+                Some(&Value::from("INVALID_ARGUMENT")),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_page_size_is_capped() {
+        let connection =
+            event_connection(Some(MAX_PAGE_SIZE as i32 + 1), None, |page| async move {
+                assert_eq!(page.limit, MAX_PAGE_SIZE as u64 + 1);
+                assert!(page.after.is_none());
+                Ok(Vec::new())
+            })
             .await
-            .expect("Could not get events from db");
+            .unwrap();
 
-        events
+        assert!(connection.edges.is_empty());
+        assert!(!connection.has_next_page);
+    }
+
+    #[tokio::test]
+    pub async fn test_bad_cursor_error_has_a_code() {
+        let pool = create_db_pool(&ServerConfig {
+            db_url: "host=localhost user=postgres".to_string(),
+            server_url: "127.0.0.1:8010".to_string(),
+            db_pool_size: None,
+            db_pool_timeout_ms: None,
+            db_connect_timeout_ms: None,
+        })
+        .unwrap();
+        let schema = Schema::build(ChronicleQuery, EmptyMutation, EmptySubscription)
+            .data(pool)
+            .data(IndexerRegistry::new(["lab9".to_string()]).unwrap())
+            .finish();
+
+        let response = schema
+            .execute(r#"{ getAllEvents(name: "lab9", after: "bad") { edges { cursor } } }"#)
+            .await;
+
+        assert_eq!(response.errors.len(), 1);
+        let code = response.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("code"));
+        assert_eq!(code, Some(&Value::from("INVALID_ARGUMENT")));
     }
 }
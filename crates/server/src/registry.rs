@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+use chronicle_primitives::db::validate_identifier;
+
+use crate::errors::ServerError;
+
+/// This is the set of event tables the server may read, one per configured indexer.
+/// The GraphQL `name` arguments are checked against it, so only configured indexers can be queried.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerRegistry {
+    tables: BTreeSet<String>,
+}
+
+impl IndexerRegistry {
+    /// Builds the registry of the configured indexers, every name must be a plain SQL identifier
+    /// params:
+    /// names: impl IntoIterator<Item = String> - The names of the indexers, these are also their table names
+    pub fn new(names: impl IntoIterator<Item = String>) -> Result<Self, anyhow::Error> {
+        let tables = names
+            .into_iter()
+            .map(|name| {
+                validate_identifier(&name)?;
+                Ok(name)
+            })
+            .collect::<Result<BTreeSet<String>, anyhow::Error>>()?;
+
+        Ok(Self { tables })
+    }
+
+    /// Returns the tables of every configured indexer
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.tables.iter().map(String::as_str)
+    }
+
+    /// Returns the table of the indexer `name`, an `UNKNOWN_INDEXER` error when no such indexer is configured
+    pub fn table(&self, name: &str) -> Result<&str, ServerError> {
+        self.tables
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| ServerError::UnknownIndexer(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_registry_only_knows_configured_indexers() {
+        let registry = IndexerRegistry::new(["lab9".to_string(), "uni".to_string()]).unwrap();
+
+        assert_eq!(registry.table("lab9").unwrap(), "lab9");
+        assert_eq!(registry.tables().collect::<Vec<_>>(), vec!["lab9", "uni"]);
+        assert_eq!(
+            registry.table("chronicle_checkpoints").unwrap_err(),
+            ServerError::UnknownIndexer("chronicle_checkpoints".to_string())
+        );
+        assert!(registry.table("lab9; DROP TABLE lab9").is_err());
+        assert!(IndexerRegistry::new(["lab 9".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use async_graphql::connection::CursorType;
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use chronicle_primitives::{
+    db::{get_filtered_events, EventPage},
+    indexer::{ChronicleEventFilter, DisplayChronicleEvent, EventCursor},
+};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::ServerError,
+    query::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+    registry::IndexerRegistry,
+};
+
+/// This is what the REST routes read events with
+#[derive(Clone)]
+pub struct RestState {
+    pub pool: Pool,
+    pub registry: IndexerRegistry,
+}
+
+/// These are the query parameters of `GET /indexers/:name/events`, lists are comma separated
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EventsParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic0: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic3: Option<String>,
+    /// This is the number of events in the page, up to `MAX_PAGE_SIZE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// This is the `next_cursor` of the previous page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+impl EventsParams {
+    /// Returns the filter selecting the events of the parameters
+    pub fn filter(&self) -> ChronicleEventFilter {
+        let list = |values: &Option<String>| {
+            values.as_ref().map(|values| {
+                values
+                    .split(',')
+                    .map(|value| value.trim().to_string())
+                    .collect()
+            })
+        };
+
+        ChronicleEventFilter {
+            chain_id: self.chain_id,
+            from_block: self.from_block,
+            to_block: self.to_block,
+            address: list(&self.address),
+            block_hash: list(&self.block_hash),
+            transaction_hash: list(&self.tx_hash),
+            topic0: list(&self.topic0),
+            topic1: list(&self.topic1),
+            topic2: list(&self.topic2),
+            topic3: list(&self.topic3),
+            from_timestamp: self.from_timestamp,
+            to_timestamp: self.to_timestamp,
+        }
+    }
+
+    /// Returns the link of the events page at `cursor`, keeping every other parameter
+    fn link(&self, name: &str, cursor: Option<String>) -> String {
+        let params = Self {
+            cursor,
+            ..self.clone()
+        };
+        let query = serde_urlencoded::to_string(&params).unwrap_or_default();
+
+        match query.is_empty() {
+            true => format!("/indexers/{name}/events"),
+            false => format!("/indexers/{name}/events?{query}"),
+        }
+    }
+}
+
+/// This is a page of events in block and log index order
+#[derive(Serialize, Debug)]
+pub struct EventsResponse {
+    pub events: Vec<DisplayChronicleEvent>,
+    /// This is the cursor of the next page, null on the last page
+    pub next_cursor: Option<String>,
+    pub links: PageLinks,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub current: String,
+    pub next: Option<String>,
+}
+
+/// This function returns the REST routes, they read events through the same queries as `ChronicleQuery`
+/// params:
+/// state: RestState - The pool and registry the events are read with
+pub fn rest_routes(state: RestState) -> Router {
+    Router::new()
+        .route("/indexers/:name/events", get(get_events))
+        .with_state(state)
+}
+
+/// This function lists the events of an indexer matching the query parameters, a page at a time
+async fn get_events(
+    State(state): State<RestState>,
+    Path(name): Path<String>,
+    Query(params): Query<EventsParams>,
+) -> Result<Json<EventsResponse>, ServerError> {
+    let table = state.registry.table(&name)?;
+    let after = params
+        .cursor
+        .as_deref()
+        .map(EventCursor::decode_cursor)
+        .transpose()
+        .map_err(ServerError::invalid_argument)?;
+    let filter = params.filter();
+    filter.validate().map_err(ServerError::invalid_argument)?;
+    let page_size = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    let mut db_client = state
+        .pool
+        .get()
+        .await
+        .map_err(ServerError::db_unavailable)?;
+    let mut events = get_filtered_events(
+        &mut db_client,
+        table,
+        &filter,
+        EventPage {
+            after,
+            limit: page_size as u64 + 1,
+        },
+    )
+    .await
+    .map_err(ServerError::db_query_failed)?;
+    let next_cursor = match events.len() > page_size {
+        true => {
+            events.truncate(page_size);
+            events
+                .last()
+                .map(|event| EventCursor::of(event).encode_cursor())
+        }
+        false => None,
+    };
+
+    Ok(Json(EventsResponse {
+        links: PageLinks {
+            current: params.link(table, params.cursor.clone()),
+            next: next_cursor
+                .clone()
+                .map(|cursor| params.link(table, Some(cursor))),
+        },
+        events,
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pool::create_db_pool;
+    use axum::http::StatusCode;
+    use chronicle_primitives::ServerConfig;
+
+    #[test]
+    pub fn test_events_params_filter_and_links() {
+        let params: EventsParams = serde_urlencoded::from_str(
+            "from_block=10&address=0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984,%200xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2&limit=5",
+        )
+        .unwrap();
+        let filter = params.filter();
+
+        assert_eq!(filter.from_block, Some(10));
+        assert_eq!(
+            filter.address,
+            Some(vec![
+                "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string(),
+                "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".to_string(),
+            ])
+        );
+        assert_eq!(filter.transaction_hash, None);
+        assert_eq!(
+            params.link("lab9", Some("12:3".to_string())),
+            "/indexers/lab9/events?from_block=10&address=0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984%2C+0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2&limit=5&cursor=12%3A3"
+        );
+        assert_eq!(
+            EventsParams::default().link("lab9", None),
+            "/indexers/lab9/events"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_malformed_filter_values_are_invalid_arguments() {
+        let config = ServerConfig {
+            db_url: "host=localhost user=postgres".to_string(),
+            server_url: "127.0.0.1:8010".to_string(),
+            db_pool_size: None,
+            db_pool_timeout_ms: None,
+            db_connect_timeout_ms: None,
+        };
+        let state = RestState {
+            pool: create_db_pool(&config).unwrap(),
+            registry: IndexerRegistry::new(["lab9".to_string()]).unwrap(),
+        };
+        let params = EventsParams {
+            tx_hash: Some("0x1234".to_string()),
+            ..Default::default()
+        };
+
+        let error = get_events(State(state), Path("lab9".to_string()), Query(params))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert!(error
+            .to_string()
+            .starts_with("Invalid argument: Invalid transaction_hash 0x1234"));
+    }
+}
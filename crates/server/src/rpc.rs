@@ -0,0 +1,757 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    primitives::{hex, LogData, U64},
+    rpc::types::eth::{BlockNumberOrTag, Filter, Log},
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    routing::post,
+    Json, Router,
+};
+use chronicle_primitives::{
+    db::{get_events_after_id, get_filtered_events, get_last_event_cursor, EventPage},
+    indexer::{ChronicleEventFilter, DisplayChronicleEvent},
+};
+use deadpool_postgres::Pool;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{errors::ServerError, registry::IndexerRegistry, subscription::last_event_id};
+
+/// This is the largest number of logs returned by a single call, larger results are rejected like archive nodes do
+pub const MAX_LOGS: usize = 10_000;
+
+/// This is the time after which a filter that has not been polled is uninstalled
+const FILTER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// This is the largest number of filters installed at once, `eth_newFilter` is rejected beyond it
+pub const MAX_FILTERS: usize = 1_024;
+
+/// This is what the JSON-RPC route reads logs with, it also holds the filters installed with `eth_newFilter`
+#[derive(Clone)]
+pub struct RpcState {
+    pub pool: Pool,
+    pub registry: IndexerRegistry,
+    filters: Arc<Mutex<LogFilters>>,
+}
+
+#[derive(Default)]
+struct LogFilters {
+    next_id: u64,
+    installed: HashMap<u64, InstalledFilter>,
+}
+
+/// This is a filter installed with `eth_newFilter`, its event filter holds the chain it was installed for
+struct InstalledFilter {
+    filter: ChronicleEventFilter,
+    /// These are the row ids of the last events read from each table, the next changes are the events stored after them
+    last_ids: HashMap<String, i64>,
+    polled_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ServerError> for RpcError {
+    fn from(error: ServerError) -> Self {
+        let code = match error {
+            ServerError::UnknownIndexer(_) | ServerError::InvalidArgument(_) => -32602,
+            ServerError::DbUnavailable | ServerError::DbQueryFailed => -32603,
+        };
+
+        Self::new(code, error.to_string())
+    }
+}
+
+/// This function returns the JSON-RPC route of every chain, it answers the log methods of the Ethereum JSON-RPC API
+/// at `/rpc/:chain_id` from the events of that chain stored by every configured indexer
+/// params:
+/// state: RpcState - The pool and registry the logs are read with
+pub fn rpc_routes(state: RpcState) -> Router {
+    Router::new()
+        .route("/rpc/:chain_id", post(handle_rpc))
+        .with_state(state)
+}
+
+/// This function answers a JSON-RPC request or a batch of them for a chain
+async fn handle_rpc(
+    State(state): State<RpcState>,
+    Path(chain_id): Path<u64>,
+    body: Bytes,
+) -> Json<Value> {
+    let body = match serde_json::from_slice::<Value>(&body) {
+        Ok(body) => body,
+        Err(e) => {
+            return Json(response(
+                Value::Null,
+                Err(RpcError::new(-32700, e.to_string())),
+            ))
+        }
+    };
+
+    match body {
+        Value::Array(requests) => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                responses.push(state.call(chain_id, request).await);
+            }
+            Json(Value::Array(responses))
+        }
+        request => Json(state.call(chain_id, request).await),
+    }
+}
+
+impl RpcState {
+    pub fn new(pool: Pool, registry: IndexerRegistry) -> Self {
+        Self {
+            pool,
+            registry,
+            filters: Arc::default(),
+        }
+    }
+
+    /// This function runs a single JSON-RPC request against the logs of a chain and returns its response
+    async fn call(&self, chain_id: u64, request: Value) -> Value {
+        let request = match serde_json::from_value::<RpcRequest>(request) {
+            Ok(request) => request,
+            Err(e) => return response(Value::Null, Err(RpcError::new(-32600, e.to_string()))),
+        };
+
+        let result = match request.method.as_str() {
+            "eth_getLogs" => match params::<(Filter,)>(request.params) {
+                Ok((filter,)) => self.get_logs(chain_id, &filter).await.and_then(to_value),
+                Err(e) => Err(e),
+            },
+            "eth_newFilter" => match params::<(Filter,)>(request.params) {
+                Ok((filter,)) => self.new_filter(chain_id, &filter).await.and_then(to_value),
+                Err(e) => Err(e),
+            },
+            "eth_getFilterChanges" => match params::<(U64,)>(request.params) {
+                Ok((id,)) => self
+                    .get_filter_changes(chain_id, id)
+                    .await
+                    .and_then(to_value),
+                Err(e) => Err(e),
+            },
+            "eth_getFilterLogs" => match params::<(U64,)>(request.params) {
+                Ok((id,)) => self.get_filter_logs(chain_id, id).await.and_then(to_value),
+                Err(e) => Err(e),
+            },
+            "eth_uninstallFilter" => match params::<(U64,)>(request.params) {
+                Ok((id,)) => to_value(self.uninstall_filter(chain_id, id)),
+                Err(e) => Err(e),
+            },
+            method => Err(RpcError::new(
+                -32601,
+                format!("the method {method} does not exist/is not available"),
+            )),
+        };
+
+        response(request.id, result)
+    }
+
+    /// This function returns the logs matching `filter`, as `eth_getLogs` does
+    async fn get_logs(&self, chain_id: u64, filter: &Filter) -> Result<Vec<Log>, RpcError> {
+        let filter = self.event_filter(chain_id, filter).await?;
+        let events = self.matching_events(&filter).await?;
+
+        events.iter().map(to_log).collect()
+    }
+
+    /// This function installs `filter` and returns its id, its changes are the logs stored from now on
+    async fn new_filter(&self, chain_id: u64, filter: &Filter) -> Result<U64, RpcError> {
+        let filter = self.event_filter(chain_id, filter).await?;
+        let mut last_ids = HashMap::new();
+        let mut db_client = self.pool.get().await.map_err(ServerError::db_unavailable)?;
+        for table in self.registry.tables() {
+            let last_id = last_event_id(&mut db_client, table)
+                .await
+                .map_err(ServerError::db_query_failed)?;
+            last_ids.insert(table.to_string(), last_id);
+        }
+
+        let id = self.filters.lock().unwrap().install(filter, last_ids)?;
+
+        Ok(U64::from(id))
+    }
+
+    /// This function returns the logs stored since the filter was installed or last polled, at most `MAX_LOGS` of them
+    async fn get_filter_changes(&self, chain_id: u64, id: U64) -> Result<Vec<Log>, RpcError> {
+        let (filter, mut last_ids) = {
+            let mut filters = self.filters.lock().unwrap();
+            let installed = filters.get(chain_id, id)?;
+            (installed.filter.clone(), installed.last_ids.clone())
+        };
+        let events = self.events_stored_after(&filter, &mut last_ids).await?;
+
+        if let Some(installed) = self.filters.lock().unwrap().installed.get_mut(&id.to()) {
+            installed.last_ids = last_ids;
+        }
+
+        events.iter().map(to_log).collect()
+    }
+
+    /// This function returns every log matching an installed filter
+    async fn get_filter_logs(&self, chain_id: u64, id: U64) -> Result<Vec<Log>, RpcError> {
+        let filter = self
+            .filters
+            .lock()
+            .unwrap()
+            .get(chain_id, id)?
+            .filter
+            .clone();
+        let events = self.matching_events(&filter).await?;
+
+        events.iter().map(to_log).collect()
+    }
+
+    fn uninstall_filter(&self, chain_id: u64, id: U64) -> bool {
+        let mut filters = self.filters.lock().unwrap();
+        let installed = filters.get(chain_id, id).is_ok();
+
+        installed && filters.installed.remove(&id.to()).is_some()
+    }
+
+    /// This function returns the events of every indexer matching `filter`, in block and log index order.
+    /// Beyond `MAX_LOGS` logs the call is rejected, like `eth_getLogs` is by archive nodes.
+    async fn matching_events(
+        &self,
+        filter: &ChronicleEventFilter,
+    ) -> Result<Vec<DisplayChronicleEvent>, RpcError> {
+        let mut db_client = self.pool.get().await.map_err(ServerError::db_unavailable)?;
+        let mut events = Vec::new();
+        for table in self.registry.tables() {
+            let page = EventPage {
+                after: None,
+                limit: MAX_LOGS as u64 + 1,
+            };
+            events.extend(
+                get_filtered_events(&mut db_client, table, filter, page)
+                    .await
+                    .map_err(ServerError::db_query_failed)?,
+            );
+        }
+        let events = sorted_logs(events);
+        if events.len() > MAX_LOGS {
+            return Err(RpcError::new(
+                -32005,
+                format!("query returned more than {MAX_LOGS} results"),
+            ));
+        }
+
+        Ok(events)
+    }
+
+    /// This function returns the events of every indexer matching `filter` stored after the rows of `last_ids`,
+    /// at most `MAX_LOGS` of them in block and log index order, and moves `last_ids` past the rows read.
+    /// Rows are read in the order they were stored, so events stored late, or again after a reorg, are not skipped
+    /// even when their block is below the changes already returned. The events left out are returned by the next call.
+    async fn events_stored_after(
+        &self,
+        filter: &ChronicleEventFilter,
+        last_ids: &mut HashMap<String, i64>,
+    ) -> Result<Vec<DisplayChronicleEvent>, RpcError> {
+        let mut db_client = self.pool.get().await.map_err(ServerError::db_unavailable)?;
+        let mut events = Vec::new();
+        for table in self.registry.tables() {
+            let mut last_id = last_ids.get(table).copied().unwrap_or_default();
+            'rows: loop {
+                let rows = get_events_after_id(&mut db_client, table, last_id, MAX_LOGS as u64)
+                    .await
+                    .map_err(ServerError::db_query_failed)?;
+                let read = rows.len();
+                for (id, event) in rows {
+                    if filter.matches(&event) {
+                        if events.len() == MAX_LOGS {
+                            break 'rows;
+                        }
+                        events.push(event);
+                    }
+                    last_id = id;
+                }
+                if read < MAX_LOGS {
+                    break;
+                }
+            }
+            last_ids.insert(table.to_string(), last_id);
+        }
+
+        Ok(sorted_logs(events))
+    }
+
+    /// This function turns an `eth_getLogs` filter into an event filter.
+    /// A missing `fromBlock`, or a tag other than `earliest`, starts at the last indexed block, a `toBlock` tag leaves the range open.
+    async fn event_filter(
+        &self,
+        chain_id: u64,
+        filter: &Filter,
+    ) -> Result<ChronicleEventFilter, RpcError> {
+        let latest_block = match starts_at_latest(filter) {
+            true => self.latest_block(chain_id).await?,
+            false => None,
+        };
+
+        let filter = to_event_filter(filter, chain_id, latest_block);
+        filter.validate().map_err(ServerError::invalid_argument)?;
+
+        Ok(filter)
+    }
+
+    /// This function returns the last block of a chain any indexer has stored an event of
+    async fn latest_block(&self, chain_id: u64) -> Result<Option<u64>, RpcError> {
+        let mut db_client = self.pool.get().await.map_err(ServerError::db_unavailable)?;
+        let mut latest_block = None;
+        for table in self.registry.tables() {
+            let cursor = get_last_event_cursor(&mut db_client, table, chain_id)
+                .await
+                .map_err(ServerError::db_query_failed)?;
+            latest_block = latest_block.max(cursor.map(|cursor| cursor.block_number));
+        }
+
+        Ok(latest_block)
+    }
+}
+
+impl LogFilters {
+    /// Installs a filter and returns its id, filters that have not been polled in time are dropped first
+    fn install(
+        &mut self,
+        filter: ChronicleEventFilter,
+        last_ids: HashMap<String, i64>,
+    ) -> Result<u64, RpcError> {
+        self.drop_expired();
+        if self.installed.len() >= MAX_FILTERS {
+            return Err(RpcError::new(
+                -32000,
+                format!("too many filters installed, at most {MAX_FILTERS}"),
+            ));
+        }
+
+        self.next_id += 1;
+        self.installed.insert(
+            self.next_id,
+            InstalledFilter {
+                filter,
+                last_ids,
+                polled_at: Instant::now(),
+            },
+        );
+
+        Ok(self.next_id)
+    }
+
+    /// Returns the filter `id` installed for a chain and marks it as polled, filters that have not been polled in time are dropped first
+    fn get(&mut self, chain_id: u64, id: U64) -> Result<&mut InstalledFilter, RpcError> {
+        self.drop_expired();
+
+        let installed = self
+            .installed
+            .get_mut(&id.to())
+            .filter(|installed| installed.filter.chain_id == Some(chain_id))
+            .ok_or_else(|| RpcError::new(-32000, "filter not found"))?;
+        installed.polled_at = Instant::now();
+
+        Ok(installed)
+    }
+
+    fn drop_expired(&mut self) {
+        self.installed
+            .retain(|_, installed| installed.polled_at.elapsed() < FILTER_TIMEOUT);
+    }
+}
+
+/// This function sorts events in block and log index order, a log indexed by more than one indexer is only kept once
+fn sorted_logs(mut events: Vec<DisplayChronicleEvent>) -> Vec<DisplayChronicleEvent> {
+    events.sort_by(|a, b| log_key(a).cmp(&log_key(b)));
+    events.dedup_by(|a, b| log_key(a) == log_key(b));
+
+    events
+}
+
+/// This function returns what tells logs apart, the same log indexed by two indexers has the same key
+fn log_key(event: &DisplayChronicleEvent) -> (u64, u64, &str) {
+    (
+        event.block_number,
+        event.log_index,
+        event.transaction_hash.as_str(),
+    )
+}
+
+/// This function returns whether the logs of a filter start at the last indexed block.
+/// Like on a node, they do when `fromBlock` is missing or a tag other than `earliest`, unless a block hash is given.
+fn starts_at_latest(filter: &Filter) -> bool {
+    filter.get_block_hash().is_none()
+        && !matches!(
+            filter.block_option.get_from_block(),
+            Some(BlockNumberOrTag::Number(_) | BlockNumberOrTag::Earliest)
+        )
+}
+
+/// This function maps an `eth_getLogs` filter onto an event filter
+/// params:
+/// filter: &Filter - The filter of the request
+/// chain_id: u64 - The chain the request is made to
+/// latest_block: Option<u64> - The block a missing `fromBlock` or a tag such as `latest` stands for
+fn to_event_filter(
+    filter: &Filter,
+    chain_id: u64,
+    latest_block: Option<u64>,
+) -> ChronicleEventFilter {
+    let strings = |values: Vec<String>| (!values.is_empty()).then_some(values);
+    let topics = |index: usize| {
+        strings(
+            filter.topics[index]
+                .iter()
+                .map(|topic| topic.to_string())
+                .collect(),
+        )
+    };
+
+    ChronicleEventFilter {
+        chain_id: Some(chain_id),
+        from_block: match filter.block_option.get_from_block() {
+            Some(BlockNumberOrTag::Number(block_number)) => Some(*block_number),
+            _ if starts_at_latest(filter) => latest_block,
+            _ => None,
+        },
+        to_block: filter.get_to_block(),
+        address: strings(
+            filter
+                .address
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+        ),
+        block_hash: filter.get_block_hash().map(|hash| vec![hash.to_string()]),
+        topic0: topics(0),
+        topic1: topics(1),
+        topic2: topics(2),
+        topic3: topics(3),
+        ..Default::default()
+    }
+}
+
+/// This function returns a stored event as an RPC log, the transaction index is not indexed so it is left out.
+/// Only stored events are logs, the events a reorg deleted are gone, so no log is ever returned with `removed` set.
+fn to_log(event: &DisplayChronicleEvent) -> Result<Log, RpcError> {
+    let log = || -> Result<Log, anyhow::Error> {
+        let topics = event
+            .topics
+            .iter()
+            .map(|topic| topic.parse())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Log {
+            inner: alloy::primitives::Log {
+                address: event.address.parse()?,
+                data: LogData::new_unchecked(topics, hex::decode(&event.data)?.into()),
+            },
+            block_hash: Some(event.block_hash.parse()?),
+            block_number: Some(event.block_number),
+            block_timestamp: event.block_timestamp,
+            transaction_hash: Some(event.transaction_hash.parse()?),
+            transaction_index: None,
+            log_index: Some(event.log_index),
+            removed: false,
+        })
+    };
+
+    log().map_err(|e| ServerError::db_query_failed(e).into())
+}
+
+/// This function parses the params of a request
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(-32602, e.to_string()))
+}
+
+fn to_value<T: Serialize>(result: T) -> Result<Value, RpcError> {
+    serde_json::to_value(result).map_err(|e| RpcError::new(-32603, e.to_string()))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(error) => (None, Some(error)),
+    };
+
+    serde_json::to_value(RpcResponse {
+        jsonrpc: "2.0",
+        id,
+        result,
+        error,
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pool::create_db_pool;
+    use alloy::primitives::{address, b256, Bytes, B256};
+    use chronicle_primitives::{
+        db::{
+            create_db_instance,
+            migrations::{run_migrations, MIGRATIONS_TABLE},
+            store_events_with_checkpoint,
+        },
+        indexer::ChronicleEvent,
+        ServerConfig,
+    };
+
+    const DB_URL: &str = "host=localhost user=postgres";
+
+    fn rpc_state(indexer: &str) -> RpcState {
+        let config = ServerConfig {
+            db_url: DB_URL.to_string(),
+            server_url: "127.0.0.1:8010".to_string(),
+            db_pool_size: None,
+            db_pool_timeout_ms: None,
+            db_connect_timeout_ms: None,
+        };
+
+        RpcState::new(
+            create_db_pool(&config).unwrap(),
+            IndexerRegistry::new([indexer.to_string()]).unwrap(),
+        )
+    }
+
+    #[test]
+    pub fn test_get_logs_filter_to_event_filter() {
+        let transfer = b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+        let filter = Filter::new()
+            .from_block(BlockNumberOrTag::Latest)
+            .to_block(19_712_000)
+            .address(address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984"))
+            .event_signature(transfer);
+        let event_filter = to_event_filter(&filter, 1, Some(19_711_184));
+
+        assert_eq!(event_filter.chain_id, Some(1));
+        assert_eq!(event_filter.from_block, Some(19_711_184));
+        assert_eq!(event_filter.to_block, Some(19_712_000));
+        assert_eq!(
+            event_filter.address,
+            Some(vec![
+                "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string()
+            ])
+        );
+        assert_eq!(event_filter.topic0, Some(vec![transfer.to_string()]));
+        assert_eq!(event_filter.topic1, None);
+
+        // Like on a node, a filter without `fromBlock` starts at the chain head
+        let event_filter = to_event_filter(&Filter::new(), 1, Some(19_711_184));
+        assert_eq!(event_filter.from_block, Some(19_711_184));
+        let event_filter = to_event_filter(
+            &Filter::new().from_block(BlockNumberOrTag::Earliest),
+            1,
+            Some(19_711_184),
+        );
+        assert_eq!(event_filter.from_block, None);
+
+        let event_filter =
+            to_event_filter(&Filter::new().at_block_hash(transfer), 1, Some(19_711_184));
+        assert_eq!(event_filter.from_block, None);
+        assert_eq!(event_filter.block_hash, Some(vec![transfer.to_string()]));
+    }
+
+    #[test]
+    pub fn test_stored_event_to_log() {
+        let event = DisplayChronicleEvent {
+            chain_id: 1,
+            address: "0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984".to_string(),
+            block_number: 19_711_184,
+            block_hash: "0x0101010101010101010101010101010101010101010101010101010101010101"
+                .to_string(),
+            transaction_hash: "0x0202020202020202020202020202020202020202020202020202020202020202"
+                .to_string(),
+            log_index: 7,
+            topics: vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+            ],
+            data: "0x69".to_string(),
+            block_timestamp: Some(1_714_000_000),
+            decoded: None,
+        };
+        let log = serde_json::to_value(to_log(&event).unwrap()).unwrap();
+
+        assert_eq!(log["address"], "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984");
+        assert_eq!(log["blockNumber"], "0x12cc4d0");
+        assert_eq!(log["logIndex"], "0x7");
+        assert_eq!(log["data"], "0x69");
+        assert_eq!(log["topics"][0], event.topics[0]);
+        assert_eq!(log["removed"], false);
+    }
+
+    #[tokio::test]
+    pub async fn test_rpc_errors_are_returned_per_request() {
+        let state = rpc_state("lab9");
+
+        let response = state
+            .call(1, serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_call", "params": [] }))
+            .await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["error"]["code"], -32601);
+
+        let response = state
+            .call(1, serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "eth_getLogs", "params": [{ "address": 1 }] }))
+            .await;
+        assert_eq!(response["error"]["code"], -32602);
+
+        let response = state
+            .call(1, serde_json::json!({ "jsonrpc": "2.0", "id": 3, "method": "eth_getFilterChanges", "params": ["0x1"] }))
+            .await;
+        assert_eq!(response["error"]["message"], "filter not found");
+
+        let response = state.call(1, serde_json::json!({ "id": 4 })).await;
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[test]
+    pub fn test_installed_filters_are_capped() {
+        let mut filters = LogFilters::default();
+        let filter = ChronicleEventFilter {
+            chain_id: Some(1),
+            ..Default::default()
+        };
+        for _ in 0..MAX_FILTERS {
+            filters.install(filter.clone(), HashMap::new()).unwrap();
+        }
+
+        let error = filters.install(filter.clone(), HashMap::new()).unwrap_err();
+        assert_eq!(error.code, -32000);
+
+        // An expired filter makes room for a new one
+        filters.installed.get_mut(&1).unwrap().polled_at =
+            Instant::now().checked_sub(FILTER_TIMEOUT).unwrap();
+        let id = filters.install(filter, HashMap::new()).unwrap();
+        assert_eq!(id, MAX_FILTERS as u64 + 1);
+
+        // A filter is only found on the chain it was installed for
+        assert!(filters.get(1, U64::from(id)).is_ok());
+        assert!(filters.get(10, U64::from(id)).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    pub async fn test_filter_changes_are_paged_per_chain() {
+        const NAME: &str = "rpc_events";
+        let mut client = create_db_instance(DB_URL)
+            .await
+            .expect("Could not create db instance");
+        client
+            .batch_execute(&format!(
+                "
+                    DROP TABLE IF EXISTS {NAME};
+                    DELETE FROM {MIGRATIONS_TABLE} WHERE scope = 'events.{NAME}';
+                "
+            ))
+            .await
+            .unwrap();
+        run_migrations(&mut client, &[NAME]).await.unwrap();
+        let state = rpc_state(NAME);
+        let call = |method: &str, params: serde_json::Value| {
+            state.call(
+                1,
+                serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }),
+            )
+        };
+
+        let id = call("eth_newFilter", serde_json::json!([{}])).await["result"].clone();
+        let events: Vec<ChronicleEvent> = (0..MAX_LOGS as u64 + 5)
+            .map(|log_index| ChronicleEvent {
+                chain_id: if log_index < 2 { 10 } else { 1 },
+                address: address!("1f9840a85d5aF5bf1D1762F925BDADdC4201F984"),
+                block_number: 100 + log_index / 100,
+                block_hash: B256::repeat_byte(1),
+                transaction_hash: B256::repeat_byte(2),
+                log_index,
+                topics: Vec::new(),
+                data: Bytes::from_static(&[0x69]),
+                block_timestamp: None,
+                decoded: Vec::new(),
+            })
+            .collect();
+        store_events_with_checkpoint(&events, 200, &mut client, NAME)
+            .await
+            .unwrap();
+
+        // The logs of other chains are left out
+        let changes = call("eth_getFilterChanges", serde_json::json!([id])).await;
+        let changes = changes["result"].as_array().unwrap();
+        assert_eq!(changes.len(), MAX_LOGS);
+        assert_eq!(changes[0]["logIndex"], "0x2");
+
+        let changes = call("eth_getFilterChanges", serde_json::json!([id])).await;
+        assert_eq!(changes["result"].as_array().unwrap().len(), 3);
+        let changes = call("eth_getFilterChanges", serde_json::json!([id])).await;
+        assert_eq!(changes["result"].as_array().unwrap().len(), 0);
+
+        // An event stored late, below the blocks already returned, is still a change
+        let late_event = ChronicleEvent {
+            block_number: 50,
+            transaction_hash: B256::repeat_byte(3),
+            log_index: 0,
+            ..events[2].clone()
+        };
+        store_events_with_checkpoint(&[late_event], 200, &mut client, NAME)
+            .await
+            .unwrap();
+        let changes = call("eth_getFilterChanges", serde_json::json!([id])).await;
+        let changes = changes["result"].as_array().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["blockNumber"], "0x32");
+
+        let logs = call(
+            "eth_getLogs",
+            serde_json::json!([{ "fromBlock": "earliest" }]),
+        )
+        .await;
+        assert_eq!(logs["error"]["code"], -32005);
+        // Without `fromBlock` only the logs of the last indexed block are returned
+        let logs = call("eth_getLogs", serde_json::json!([{}])).await;
+        assert_eq!(logs["result"].as_array().unwrap().len(), 5);
+        let logs = state
+            .call(
+                10,
+                serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_getLogs", "params": [{ "fromBlock": "earliest" }] }),
+            )
+            .await;
+        assert_eq!(logs["result"].as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_graphql::{Context, ResultExt, SimpleObject, Subscription, Union};
+use chronicle_primitives::{
+    db::{
+        get_block_events_up_to_id, get_events_after_id, get_last_event_id,
+        notifications::{listen_for_events, EventNotification},
+        EventPage,
+    },
+    indexer::{ChronicleEventFilter, DisplayChronicleEvent, EventCursor},
+};
+use deadpool_postgres::Pool;
+use futures_util::{stream, Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_postgres::error::SqlState;
+
+use crate::{errors::ServerError, registry::IndexerRegistry};
+
+/// This is the number of changes kept for subscribers that fall behind, older changes are skipped
+pub const EVENT_BROADCAST_CAPACITY: usize = 1_024;
+
+/// This is the number of events read from the db at a time while broadcasting them
+const EVENT_READ_BATCH: u64 = 500;
+
+/// This is the time waited before listening again once the listening connection is lost
+const RELISTEN_DELAY: Duration = Duration::from_secs(5);
+
+/// This announces that the events of a block range were removed, e.g. by a reorg.
+/// The events stored in these blocks since then follow it.
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub struct RemovedEvents {
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+impl RemovedEvents {
+    /// Returns whether some of the removed blocks are in the block range of `filter`
+    pub fn overlaps(&self, filter: &ChronicleEventFilter) -> bool {
+        filter
+            .from_block
+            .into_iter()
+            .all(|from| self.to_block >= from)
+            && filter.to_block.into_iter().all(|to| self.from_block <= to)
+    }
+}
+
+/// This is a change to the events of an indexer
+#[derive(Union, Debug, Clone)]
+pub enum EventChange {
+    Stored(DisplayChronicleEvent),
+    Removed(RemovedEvents),
+}
+
+/// This is a change to the events of an indexer, as it is broadcast
+#[derive(Debug, Clone)]
+pub struct IndexedEvent {
+    pub indexer: String,
+    pub change: EventChange,
+}
+
+/// This fans the changes to the stored events out to every subscriber
+#[derive(Debug, Clone)]
+pub struct EventBroadcast(broadcast::Sender<Arc<IndexedEvent>>);
+
+impl EventBroadcast {
+    /// This function listens for the events stored and removed by the indexers of `registry` and broadcasts them.
+    /// Events are only read from the db while there are subscribers, and listening restarts when the connection is lost,
+    /// the events stored in the meantime are broadcast once it listens again.
+    /// params:
+    /// db_url: String - The URL of the database
+    /// pool: Pool - The pool of db connections the events are read with
+    /// registry: IndexerRegistry - The indexers whose events are broadcast
+    pub fn spawn(db_url: String, pool: Pool, registry: IndexerRegistry) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+        let broadcast = Self(sender.clone());
+
+        tokio::spawn(async move {
+            // These are the row ids of the last events broadcast per table, events stored before the server are skipped
+            let mut last_ids = HashMap::new();
+            loop {
+                match listen_for_events(&db_url).await {
+                    Ok((_client, notifications)) => {
+                        // The events stored while not listening are caught up on before the notifications
+                        let caught_up: Vec<_> = registry
+                            .tables()
+                            .map(|table| EventNotification::Stored {
+                                indexer: table.to_string(),
+                            })
+                            .collect();
+                        let received = stream::unfold(notifications, |mut notifications| async {
+                            let notification = notifications.recv().await?;
+                            Some((notification, notifications))
+                        });
+                        let mut notifications = stream::iter(caught_up).chain(received).boxed();
+                        while let Some(notification) = notifications.next().await {
+                            if registry.table(notification.indexer()).is_err() {
+                                continue;
+                            }
+                            if let Err(e) =
+                                relay_notification(&sender, &pool, &mut last_ids, &notification)
+                                    .await
+                            {
+                                tracing::error!(
+                                    "Could not broadcast the events of indexer {}: {}",
+                                    notification.indexer(),
+                                    e
+                                );
+                            }
+                        }
+                        tracing::warn!("Lost the connection listening for stored events");
+                    }
+                    Err(e) => tracing::error!("Could not listen for stored events: {}", e),
+                }
+                tokio::time::sleep(RELISTEN_DELAY).await;
+            }
+        });
+
+        broadcast
+    }
+
+    /// Returns the stream of the changes from now on, changes missed by a lagging subscriber are skipped
+    pub fn subscribe(&self) -> impl Stream<Item = Arc<IndexedEvent>> {
+        stream::unfold(self.0.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("A subscriber skipped {} changes", skipped)
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+/// This function broadcasts the events a notification announces.
+/// Stored events are read after the last row broadcast. Removed blocks are announced, followed by the events
+/// already broadcast that are stored in them now, since the notification of their storing may have come first.
+/// params:
+/// sender: &Sender - The sender of the broadcast
+/// pool: &Pool - The pool of db connections the events are read with
+/// last_ids: &mut HashMap - The row ids of the last events broadcast per table
+/// notification: &EventNotification - The change to the events of an indexer
+async fn relay_notification(
+    sender: &broadcast::Sender<Arc<IndexedEvent>>,
+    pool: &Pool,
+    last_ids: &mut HashMap<String, i64>,
+    notification: &EventNotification,
+) -> Result<(), anyhow::Error> {
+    let mut db_client = pool.get().await?;
+    let indexer = notification.indexer().to_string();
+    let last_id = match last_ids.get(&indexer) {
+        Some(last_id) => *last_id,
+        None => last_event_id(&mut db_client, &indexer).await?,
+    };
+    last_ids.insert(indexer.clone(), last_id);
+    let send = |change| {
+        let _ = sender.send(Arc::new(IndexedEvent {
+            indexer: indexer.clone(),
+            change,
+        }));
+    };
+
+    match *notification {
+        EventNotification::Stored { .. } => {
+            if sender.receiver_count() == 0 {
+                // Nobody is waiting for these events, broadcasting restarts after them
+                let last_id = last_event_id(&mut db_client, &indexer).await?;
+                last_ids.insert(indexer.clone(), last_id);
+                return Ok(());
+            }
+            let mut last_id = last_id;
+            loop {
+                let events =
+                    get_events_after_id(&mut db_client, &indexer, last_id, EVENT_READ_BATCH)
+                        .await?;
+                let read = events.len() as u64;
+                for (id, event) in events {
+                    last_id = id;
+                    send(EventChange::Stored(event));
+                }
+                last_ids.insert(indexer.clone(), last_id);
+                if read < EVENT_READ_BATCH {
+                    return Ok(());
+                }
+            }
+        }
+        EventNotification::Removed {
+            from_block,
+            to_block,
+            ..
+        } => {
+            if sender.receiver_count() == 0 {
+                return Ok(());
+            }
+            send(EventChange::Removed(RemovedEvents {
+                from_block,
+                to_block,
+            }));
+            let mut page = EventPage {
+                after: None,
+                limit: EVENT_READ_BATCH,
+            };
+            loop {
+                let events = get_block_events_up_to_id(
+                    &mut db_client,
+                    &indexer,
+                    from_block,
+                    to_block,
+                    last_id,
+                    page,
+                )
+                .await?;
+                let read = events.len() as u64;
+                page.after = events.last().map(EventCursor::of);
+                for event in events {
+                    send(EventChange::Stored(event));
+                }
+                if read < EVENT_READ_BATCH {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// This function returns the row id of the last event stored by an indexer, 0 when it stored none.
+/// A table created after the server started holds no events from before it, so a missing table has none.
+pub(crate) async fn last_event_id(
+    db_client: &mut tokio_postgres::Client,
+    indexer: &str,
+) -> Result<i64, anyhow::Error> {
+    match get_last_event_id(db_client, indexer).await {
+        Ok(last_id) => Ok(last_id.unwrap_or_default()),
+        Err(e)
+            if e.downcast_ref::<tokio_postgres::Error>()
+                .and_then(tokio_postgres::Error::code)
+                == Some(&SqlState::UNDEFINED_TABLE) =>
+        {
+            Ok(0)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub struct ChronicleSubscription;
+
+#[Subscription]
+impl ChronicleSubscription {
+    /// Streams the changes to the events of an indexer: the events matching every condition of `where` as they are stored,
+    /// and the removal of the blocks in its block range, e.g. by a reorg, which is followed by the events stored in their place
+    async fn events<'a>(
+        &self,
+        cxt: &Context<'a>,
+        name: String,
+        #[graphql(name = "where")] filter: Option<ChronicleEventFilter>,
+    ) -> async_graphql::Result<impl Stream<Item = EventChange>> {
+        let name = cxt
+            .data_unchecked::<IndexerRegistry>()
+            .table(&name)
+            .extend()?
+            .to_string();
+        let filter = filter.unwrap_or_default();
+        filter
+            .validate()
+            .map_err(ServerError::invalid_argument)
+            .extend()?;
+
+        Ok(cxt
+            .data_unchecked::<EventBroadcast>()
+            .subscribe()
+            .filter_map(move |indexed| {
+                let relevant = indexed.indexer == name
+                    && match &indexed.change {
+                        EventChange::Stored(event) => filter.matches(event),
+                        EventChange::Removed(removed) => removed.overlaps(&filter),
+                    };
+                let change = relevant.then(|| indexed.change.clone());
+                async move { change }
+            }))
+    }
+}
@@ -0,0 +1,596 @@
+use std::{collections::HashSet, sync::Arc};
+
+use alloy::{
+    json_abi::Event,
+    primitives::{hex, B256},
+};
+use anyhow::{anyhow, bail};
+use async_graphql::{
+    dynamic::{
+        Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ResolverContext,
+        Scalar, Schema, TypeRef, ValueAccessor,
+    },
+    ResultExt, Value,
+};
+use chronicle_primitives::{
+    abi::{ColumnType, DecodedColumn, IndexerAbi},
+    db::decoded::{
+        event_table_columns, get_decoded_events, ColumnFilter, Comparison, DecodedEventRow,
+        DecodedEventsQuery, SortDirection,
+    },
+};
+use deadpool_postgres::Pool;
+
+use crate::errors::ServerError;
+
+/// This is the largest number of events returned by a single typed query, it is also the default page size
+pub const MAX_TYPED_PAGE_SIZE: u64 = 1_000;
+
+/// The scalar holding integers too large for `Int`, as decimal strings
+const BIG_INT_SCALAR: &str = "BigInt";
+
+/// The scalar holding arrays and tuples
+const JSON_SCALAR: &str = "JSON";
+
+/// The enum picking the direction typed queries are sorted in
+const ORDER_DIRECTION_ENUM: &str = "OrderDirection";
+
+/// The comparisons of a filter field, keyed by the suffix of the field name
+const COMPARISONS: &[(&str, Comparison)] = &[
+    ("", Comparison::Eq),
+    ("_in", Comparison::In),
+    ("_gt", Comparison::Gt),
+    ("_gte", Comparison::Gte),
+    ("_lt", Comparison::Lt),
+    ("_lte", Comparison::Lte),
+];
+
+/// This is an indexer whose ABI events are exposed by the typed schema
+#[derive(Debug, Clone)]
+pub struct TypedIndexer {
+    /// This is the name of the indexer, this is also the name of its event table
+    pub name: String,
+    pub abi: IndexerAbi,
+}
+
+/// This function builds the typed GraphQL schema of the indexers.
+/// Every ABI event gets its own type, e.g. `Transfer { from, to, value }`, and a query field such as `transfers`
+/// taking `where`, `orderBy`, `orderDirection`, `first` and `skip` arguments.
+/// params:
+/// pool: Pool - The pool of db connections the events are read with
+/// indexers: &[TypedIndexer] - The indexers exposed by the schema
+pub fn build_typed_schema(pool: Pool, indexers: &[TypedIndexer]) -> Result<Schema, anyhow::Error> {
+    let names: Vec<String> = indexers
+        .iter()
+        .map(|indexer| indexer.name.clone())
+        .collect();
+    let mut query = Object::new("Query").field(
+        Field::new(
+            "indexers",
+            TypeRef::named_nn_list_nn(TypeRef::STRING),
+            move |_| {
+                let names = names.clone();
+                FieldFuture::new(async move {
+                    Ok(Some(FieldValue::list(
+                        names.into_iter().map(FieldValue::value),
+                    )))
+                })
+            },
+        )
+        .description("The names of the indexers served by this schema"),
+    );
+
+    let mut schema = Schema::build("Query", None, None)
+        .register(
+            Scalar::new(BIG_INT_SCALAR)
+                .description("An integer of arbitrary size, written as a decimal string"),
+        )
+        .register(Scalar::new(JSON_SCALAR))
+        .register(Enum::new(ORDER_DIRECTION_ENUM).item("asc").item("desc"));
+
+    let mut type_names = HashSet::new();
+    for indexer in indexers {
+        for (selector, event) in &indexer.abi.events {
+            let typed_event =
+                Arc::new(TypedEvent::new(indexer, *selector, event, &mut type_names)?);
+            query = query.field(typed_event.query_field());
+            schema = schema
+                .register(typed_event.object())
+                .register(typed_event.filter())
+                .register(typed_event.order_by());
+        }
+    }
+
+    Ok(schema.register(query).data(pool).finish()?)
+}
+
+/// This is an ABI event and the GraphQL fields of its type
+struct TypedEvent {
+    /// This is the name of the event table
+    table: String,
+    selector: B256,
+    type_name: String,
+    fields: Vec<TypedField>,
+}
+
+/// This is a field of an event type and the column it is read from
+struct TypedField {
+    name: String,
+    column: DecodedColumn,
+    nullable: bool,
+}
+
+impl TypedEvent {
+    fn new(
+        indexer: &TypedIndexer,
+        selector: B256,
+        event: &Event,
+        type_names: &mut HashSet<String>,
+    ) -> Result<Self, anyhow::Error> {
+        // Events of different indexers may share a name, the later ones are prefixed with their indexer name
+        let mut type_name = to_pascal_case(&event.name);
+        if !type_names.insert(type_name.clone()) {
+            type_name = format!("{}{type_name}", to_pascal_case(&indexer.name));
+            if !type_names.insert(type_name.clone()) {
+                bail!(
+                    "The {} event of indexer {} is configured twice",
+                    event.name,
+                    indexer.name
+                );
+            }
+        }
+
+        let table_fields = event_table_columns().into_iter().map(|column| TypedField {
+            name: to_camel_case(&column.name),
+            nullable: column.name == "block_timestamp",
+            column,
+        });
+        // Parameters that could not be decoded are null
+        let param_fields = indexer
+            .abi
+            .event_columns(event)?
+            .into_iter()
+            .map(|column| TypedField {
+                name: to_camel_case(&column.name),
+                nullable: true,
+                column,
+            });
+
+        Ok(Self {
+            table: indexer.name.clone(),
+            selector,
+            type_name,
+            fields: table_fields.chain(param_fields).collect(),
+        })
+    }
+
+    fn filter_name(&self) -> String {
+        format!("{}Filter", self.type_name)
+    }
+
+    fn order_by_name(&self) -> String {
+        format!("{}OrderBy", self.type_name)
+    }
+
+    /// Returns the object type of the event
+    fn object(&self) -> Object {
+        self.fields
+            .iter()
+            .fold(Object::new(&self.type_name), |object, field| {
+                let column = field.column.name.clone();
+                let type_name = graphql_type(field.column.column_type);
+                let ty = match field.nullable {
+                    true => TypeRef::named(type_name),
+                    false => TypeRef::named_nn(type_name),
+                };
+
+                object.field(Field::new(&field.name, ty, move |ctx| {
+                    let column = column.clone();
+                    FieldFuture::new(async move {
+                        let event = ctx.parent_value.try_downcast_ref::<DecodedEventRow>()?;
+                        let value = match event.get(&column) {
+                            Some(serde_json::Value::Null) | None => None,
+                            Some(value) => Some(Value::from_json(value.clone())?),
+                        };
+
+                        Ok(value.map(FieldValue::value))
+                    })
+                }))
+            })
+    }
+
+    /// Returns the filter input of the event, every field can be compared for equality and
+    /// integer fields can also be compared with `_gt`, `_gte`, `_lt` and `_lte`
+    fn filter(&self) -> InputObject {
+        let mut filter = InputObject::new(self.filter_name());
+        for field in &self.fields {
+            let type_name = graphql_type(field.column.column_type);
+            for (suffix, comparison) in comparisons(field.column.column_type) {
+                let ty = match comparison {
+                    Comparison::In => TypeRef::named_nn_list(type_name),
+                    _ => TypeRef::named(type_name),
+                };
+                filter = filter.field(InputValue::new(format!("{}{suffix}", field.name), ty));
+            }
+        }
+
+        filter
+    }
+
+    /// Returns the enum of the fields the events can be sorted by
+    fn order_by(&self) -> Enum {
+        self.fields
+            .iter()
+            .filter(|field| field.column.column_type != ColumnType::Jsonb)
+            .fold(Enum::new(self.order_by_name()), |order_by, field| {
+                order_by.item(&field.name)
+            })
+    }
+
+    /// Returns the query field listing the events, e.g. `transfers` for the `Transfer` type
+    fn query_field(self: &Arc<Self>) -> Field {
+        let event = self.clone();
+        let field_name = format!("{}s", to_camel_case(&self.type_name));
+
+        Field::new(
+            field_name,
+            TypeRef::named_nn_list_nn(&self.type_name),
+            move |ctx| {
+                let event = event.clone();
+                FieldFuture::new(async move {
+                    let query = event.query(&ctx)?;
+                    let mut db_client = ctx
+                        .data::<Pool>()?
+                        .get()
+                        .await
+                        .map_err(ServerError::db_unavailable)
+                        .extend()?;
+                    let events = get_decoded_events(&mut db_client, &event.table, &query)
+                        .await
+                        .map_err(ServerError::db_query_failed)
+                        .extend()?;
+
+                    Ok(Some(FieldValue::list(
+                        events.into_iter().map(FieldValue::owned_any),
+                    )))
+                })
+            },
+        )
+        .argument(InputValue::new("where", TypeRef::named(self.filter_name())))
+        .argument(InputValue::new(
+            "orderBy",
+            TypeRef::named(self.order_by_name()),
+        ))
+        .argument(InputValue::new(
+            "orderDirection",
+            TypeRef::named(ORDER_DIRECTION_ENUM),
+        ))
+        .argument(InputValue::new("first", TypeRef::named(TypeRef::INT)))
+        .argument(InputValue::new("skip", TypeRef::named(TypeRef::INT)))
+    }
+
+    /// This function reads the arguments of the query field into a query of the event table
+    fn query(&self, ctx: &ResolverContext<'_>) -> Result<DecodedEventsQuery, async_graphql::Error> {
+        let mut filters = Vec::new();
+        if let Some(filter) = argument(ctx, "where") {
+            for (name, value) in filter.object()?.iter() {
+                if value.is_null() {
+                    continue;
+                }
+                let (field, comparison) = self
+                    .filter_field(name.as_str())
+                    .ok_or_else(|| {
+                        ServerError::invalid_argument(format!("Unknown filter field {name}"))
+                    })
+                    .extend()?;
+                let column_type = field.column.column_type;
+                let values = match comparison {
+                    Comparison::In => value
+                        .list()?
+                        .iter()
+                        .map(|value| input_text(value.as_value(), column_type))
+                        .collect::<Result<Vec<_>, _>>(),
+                    _ => input_text(value.as_value(), column_type).map(|value| vec![value]),
+                }
+                .map_err(|e| ServerError::invalid_argument(format!("Invalid value of {name}: {e}")))
+                .extend()?;
+
+                filters.push(ColumnFilter {
+                    column: field.column.clone(),
+                    comparison,
+                    values,
+                });
+            }
+        }
+
+        let order_by = match argument(ctx, "orderBy") {
+            Some(order_by) => {
+                let name = order_by.enum_name()?;
+                self.fields
+                    .iter()
+                    .find(|field| field.name == name)
+                    .map(|field| field.column.clone())
+            }
+            None => None,
+        };
+        let direction = match argument(ctx, "orderDirection") {
+            Some(direction) if direction.enum_name()? == "desc" => SortDirection::Desc,
+            _ => SortDirection::Asc,
+        };
+        let limit = match argument(ctx, "first") {
+            Some(first) => page_argument(first, "first")?.min(MAX_TYPED_PAGE_SIZE),
+            None => MAX_TYPED_PAGE_SIZE,
+        };
+        let offset = match argument(ctx, "skip") {
+            Some(skip) => page_argument(skip, "skip")?,
+            None => 0,
+        };
+
+        Ok(DecodedEventsQuery {
+            selector: self.selector,
+            columns: self
+                .fields
+                .iter()
+                .map(|field| field.column.clone())
+                .collect(),
+            filters,
+            order_by,
+            direction,
+            limit,
+            offset,
+        })
+    }
+
+    /// Returns the field and comparison of a filter field name, e.g. `value_gte`
+    fn filter_field(&self, name: &str) -> Option<(&TypedField, Comparison)> {
+        self.fields.iter().find_map(|field| {
+            let suffix = name.strip_prefix(field.name.as_str())?;
+            comparisons(field.column.column_type)
+                .find(|(comparison_suffix, _)| *comparison_suffix == suffix)
+                .map(|(_, comparison)| (field, comparison))
+        })
+    }
+}
+
+/// Returns the GraphQL type of a column
+fn graphql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Bytea | ColumnType::Text => TypeRef::STRING,
+        ColumnType::BigInt => TypeRef::INT,
+        ColumnType::Numeric => BIG_INT_SCALAR,
+        ColumnType::Boolean => TypeRef::BOOLEAN,
+        ColumnType::Jsonb => JSON_SCALAR,
+    }
+}
+
+/// Returns the comparisons a column can be filtered with, only integers are ordered and JSON can't be filtered
+fn comparisons(column_type: ColumnType) -> impl Iterator<Item = (&'static str, Comparison)> {
+    let count = match column_type {
+        ColumnType::BigInt | ColumnType::Numeric => COMPARISONS.len(),
+        ColumnType::Bytea | ColumnType::Text => 2,
+        ColumnType::Boolean => 1,
+        ColumnType::Jsonb => 0,
+    };
+
+    COMPARISONS.iter().take(count).copied()
+}
+
+/// Returns the argument `name` of a field, None when it is missing or null
+fn argument<'a>(ctx: &'a ResolverContext<'_>, name: &str) -> Option<ValueAccessor<'a>> {
+    ctx.args.get(name).filter(|value| !value.is_null())
+}
+
+/// Returns the value of a `first` or `skip` argument, which can't be negative
+fn page_argument(value: ValueAccessor<'_>, name: &str) -> Result<u64, async_graphql::Error> {
+    value
+        .u64()
+        .map_err(|_| ServerError::invalid_argument(format!("{name} must not be negative")))
+        .extend()
+}
+
+/// Returns the text of a filter value, after checking it is a value of the column it is compared with
+fn input_text(value: &Value, column_type: ColumnType) -> Result<String, anyhow::Error> {
+    let text = match value {
+        Value::String(value) => value.clone(),
+        Value::Number(value) => value.to_string(),
+        Value::Boolean(value) => value.to_string(),
+        value => bail!("unsupported filter value {value}"),
+    };
+    match column_type {
+        ColumnType::Bytea => {
+            hex::decode(&text).map_err(|e| anyhow!("{text} is not hex: {e}"))?;
+        }
+        ColumnType::BigInt => {
+            text.parse::<i64>()
+                .map_err(|e| anyhow!("{text} is not an integer: {e}"))?;
+        }
+        ColumnType::Numeric => {
+            let digits = text.strip_prefix('-').unwrap_or(&text);
+            if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+                bail!("{text} is not a decimal integer");
+            }
+        }
+        ColumnType::Boolean => {
+            text.parse::<bool>()
+                .map_err(|e| anyhow!("{text} is not a boolean: {e}"))?;
+        }
+        ColumnType::Text => {}
+        ColumnType::Jsonb => bail!("JSON values can't be filtered"),
+    }
+
+    Ok(text)
+}
+
+/// This function turns a name into a GraphQL type name, e.g. `token_transfer` becomes `TokenTransfer`
+fn to_pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// This function turns a column name into a GraphQL field name, e.g. `token_id` becomes `tokenId`
+fn to_camel_case(name: &str) -> String {
+    let pascal_case = to_pascal_case(name);
+    let mut chars = pascal_case.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::pool::create_db_pool;
+    use chronicle_primitives::ServerConfig;
+
+    fn transfer_indexer() -> TypedIndexer {
+        let mut abi = IndexerAbi::default();
+        abi.add_signature("Transfer(address indexed from,address indexed to,uint256 value)")
+            .unwrap();
+
+        TypedIndexer {
+            name: "token_events".to_string(),
+            abi,
+        }
+    }
+
+    fn typed_schema() -> Schema {
+        let pool = create_db_pool(&ServerConfig {
+            db_url: "host=localhost user=postgres".to_string(),
+            server_url: "127.0.0.1:8010".to_string(),
+            db_pool_size: None,
+            db_pool_timeout_ms: None,
+            db_connect_timeout_ms: None,
+        })
+        .unwrap();
+
+        build_typed_schema(pool, &[transfer_indexer()]).unwrap()
+    }
+
+    fn transfer_event() -> TypedEvent {
+        let indexer = transfer_indexer();
+        let (selector, event) = indexer.abi.events.iter().next().unwrap();
+
+        TypedEvent::new(&indexer, *selector, event, &mut HashSet::new()).unwrap()
+    }
+
+    #[test]
+    pub fn test_case_helpers() {
+        assert_eq!(to_pascal_case("token_transfer"), "TokenTransfer");
+        assert_eq!(to_pascal_case("Transfer"), "Transfer");
+        assert_eq!(to_pascal_case("_id"), "Id");
+        assert_eq!(to_camel_case("token_id"), "tokenId");
+        assert_eq!(to_camel_case("TokenTransfer"), "tokenTransfer");
+        assert_eq!(to_camel_case(""), "");
+    }
+
+    #[test]
+    pub fn test_comparisons_depend_on_the_column_type() {
+        let suffixes = |column_type| {
+            comparisons(column_type)
+                .map(|(suffix, _)| suffix)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            suffixes(ColumnType::Numeric),
+            vec!["", "_in", "_gt", "_gte", "_lt", "_lte"]
+        );
+        assert_eq!(suffixes(ColumnType::Bytea), vec!["", "_in"]);
+        assert_eq!(suffixes(ColumnType::Boolean), vec![""]);
+        assert!(suffixes(ColumnType::Jsonb).is_empty());
+    }
+
+    #[test]
+    pub fn test_filter_field_parses_the_comparison_suffix() {
+        let event = transfer_event();
+
+        let (field, comparison) = event.filter_field("value_gte").unwrap();
+        assert_eq!(field.column.name, "value");
+        assert_eq!(comparison, Comparison::Gte);
+        let (field, comparison) = event.filter_field("blockNumber_in").unwrap();
+        assert_eq!(field.column.name, "block_number");
+        assert_eq!(comparison, Comparison::In);
+        let (field, comparison) = event.filter_field("from").unwrap();
+        assert_eq!(field.column.name, "from");
+        assert_eq!(comparison, Comparison::Eq);
+
+        // Bytes are not ordered, and unknown fields or suffixes match nothing
+        assert!(event.filter_field("from_gt").is_none());
+        assert!(event.filter_field("value_between").is_none());
+        assert!(event.filter_field("amount").is_none());
+    }
+
+    #[test]
+    pub fn test_filter_values_are_checked_against_the_column_type() {
+        let text = |value: Value, column_type| input_text(&value, column_type);
+
+        assert_eq!(
+            text(Value::from("0xAB"), ColumnType::Bytea).unwrap(),
+            "0xAB"
+        );
+        assert!(text(Value::from("0xzz"), ColumnType::Bytea).is_err());
+        assert_eq!(
+            text(Value::from("-1000"), ColumnType::Numeric).unwrap(),
+            "-1000"
+        );
+        assert!(text(Value::from("1e18"), ColumnType::Numeric).is_err());
+        assert!(text(Value::from(""), ColumnType::Numeric).is_err());
+        assert_eq!(text(Value::from(5), ColumnType::BigInt).unwrap(), "5");
+        assert!(text(Value::from("five"), ColumnType::BigInt).is_err());
+        assert_eq!(
+            text(Value::from(true), ColumnType::Boolean).unwrap(),
+            "true"
+        );
+        assert!(text(Value::from("[]"), ColumnType::Jsonb).is_err());
+    }
+
+    #[test]
+    pub fn test_typed_schema_from_abi() {
+        let sdl = typed_schema().sdl();
+
+        assert!(sdl.contains("type Transfer {"));
+        assert!(sdl.contains("value: BigInt"));
+        assert!(sdl.contains("from: String"));
+        assert!(sdl.contains("blockNumber: Int!"));
+        assert!(sdl.contains("input TransferFilter {"));
+        assert!(sdl.contains("value_gte: BigInt"));
+        assert!(sdl.contains("from_in: [String!]"));
+        assert!(!sdl.contains("from_gt"));
+        assert!(sdl.contains("enum TransferOrderBy {"));
+        assert!(sdl.contains("transfers(where: TransferFilter"));
+    }
+
+    #[tokio::test]
+    pub async fn test_malformed_filter_values_are_invalid_arguments() {
+        let schema = typed_schema();
+
+        for query in [
+            r#"{ transfers(where: { from: "0xzz" }) { value } }"#,
+            r#"{ transfers(where: { value_gte: "a lot" }) { value } }"#,
+            r#"{ transfers(first: -1) { value } }"#,
+        ] {
+            let response = schema.execute(query).await;
+
+            assert_eq!(response.errors.len(), 1, "{query}");
+            let code = response.errors[0]
+                .extensions
+                .as_ref()
+                .and_then(|extensions| extensions.get("code"));
+            assert_eq!(
+                code,
+                Some(&Value::from("INVALID_ARGUMENT")),
+                "{:?}",
+                response.errors
+            );
+        }
+    }
+}
@@ -1,48 +1,126 @@
 use crate::Task;
-use alloy::{providers::ProviderBuilder, rpc::client::WsConnect};
-use anyhow::bail;
+use alloy::{
+    providers::ProviderBuilder,
+    rpc::{
+        client::{IpcConnect, WsConnect},
+        types::eth::Filter,
+    },
+};
+use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use chronicle_indexer::events::evm::EvmEventIndexer;
+use chronicle_indexer::events::evm::{
+    backfill::{BackfillConfig, DEFAULT_BACKFILL_CHUNK_SIZE, DEFAULT_BACKFILL_WORKERS},
+    finality::Finality,
+    reconnect::ReconnectBackoff,
+    transport::{ipc_path, RpcTransport, DEFAULT_POLL_INTERVAL},
+    utils::event_filter,
+    EvmEventIndexer,
+};
 use chronicle_primitives::{
-    db::create_db_instance, interfaces::ChronicleEventIndexer, IndexerConfig, StateMachine,
+    abi::IndexerAbi,
+    db::{
+        add_decoded_columns,
+        checkpoint::{get_checkpoint, resolve_start_block},
+        create_db_instance,
+        migrations::run_migrations,
+        validate_identifier,
+    },
+    interfaces::ChronicleEventIndexer,
+    IndexerConfig, StateMachine,
 };
+use std::{sync::Arc, time::Duration};
 use tokio::{select, try_join};
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Debug)]
 pub struct IndexerTask {
     pub config: IndexerConfig,
+    /// When set, the stored events and checkpoint are dropped and indexing restarts from the configured block,
+    /// otherwise indexing resumes from the last checkpointed block
+    pub rebuild: bool,
 }
 
 #[async_trait]
 impl Task for IndexerTask {
     async fn run(mut self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
+        // The indexer name is also the name of its event table
+        validate_identifier(&self.config.event_name)?;
         let mut client = create_db_instance(&self.config.db_url)
             .await
             .expect("Could not create db instance");
-        let ws = WsConnect::new(self.config.rpc_url.clone());
-        let provider = ProviderBuilder::new().on_ws(ws).await.unwrap();
+        let applied = run_migrations(&mut client, &[&self.config.event_name]).await?;
+        info!(
+            "Applied {} pending migration(s) for indexer {}",
+            applied, self.config.event_name
+        );
+        if self.rebuild {
+            warn!(
+                "Rebuilding indexer {}: every stored event and its checkpoint are being DROPPED, indexing restarts from block {}",
+                self.config.event_name, self.config.block_number
+            );
+        }
+        let start_block = resolve_start_block(
+            &mut client,
+            &self.config.event_name,
+            self.config.block_number,
+            self.rebuild,
+        )
+        .await?;
+        info!(
+            "Indexer {} starting from block {}",
+            self.config.event_name, start_block
+        );
         match self.config.state_machine.clone().into() {
             StateMachine::EVM => {
-                let evm_event_indexer = EvmEventIndexer::new(self.config.event_name.clone());
+                let finality = Finality::new(
+                    self.config.confirmations,
+                    self.config.finality_tag.as_deref(),
+                )?;
+                let backfill = BackfillConfig {
+                    chunk_size: self
+                        .config
+                        .backfill_chunk_size
+                        .unwrap_or(DEFAULT_BACKFILL_CHUNK_SIZE),
+                    workers: self
+                        .config
+                        .backfill_workers
+                        .unwrap_or(DEFAULT_BACKFILL_WORKERS),
+                    requests_per_second: self.config.backfill_requests_per_second,
+                };
+                let transport = RpcTransport::from_url(&self.config.rpc_url)?;
+                // Every address, event and topic of the indexer is served by one filter
+                let abi = IndexerAbi::from_config(&self.config)?;
+                // Decoded event parameters are stored in their own columns next to the raw log
+                add_decoded_columns(&mut client, &self.config.event_name, &abi.columns()?).await?;
+                let filter = event_filter(
+                    &self.config.address,
+                    &abi.selectors,
+                    [
+                        &self.config.topic1,
+                        &self.config.topic2,
+                        &self.config.topic3,
+                    ],
+                )?;
+                let evm_event_indexer = EvmEventIndexer::new(
+                    self.config.event_name.clone(),
+                    finality,
+                    backfill,
+                    Arc::new(abi),
+                );
 
                 // This queries events that have happened from this block number and stores them in the database
-                // It also subscribes to new events and stores them in the database
+                // It also follows new events and stores them in the database, reconnecting whenever the connection drops
                 let evm_indexer_handle = tokio::spawn(async move {
                     select! {
-                        event_n_sub = evm_event_indexer.query_events(
-                            provider.clone(),
-                            self.config.address.clone().parse().expect("CONFIG address could not be parsed"),
-                            self.config.event_signature.clone().parse().expect("CONFIG event signature is missing"),
-                            self.config.block_number.into(),
+                        _ = index_with_reconnect(
+                            &self.config,
+                            &evm_event_indexer,
+                            transport,
+                            filter,
+                            start_block,
                             &mut client,
-                        ) => {
-                            // Want this indexing to halt before
-                            if event_n_sub.is_err() {
-                                info!("Event subscription error, exitting now. ERROR: {:?}", event_n_sub.err().unwrap());
-                            }
-                        }
+                        ) => {}
                         _ = shutdown_token.cancelled() => {
                             info!("Shutting down chronicle indexer");
                         }
@@ -62,9 +140,113 @@ impl Task for IndexerTask {
     }
 }
 
+/// This function runs the indexer until shutdown, reconnecting whenever the provider connection drops.
+/// Every reconnect waits an exponentially growing delay and resumes from the last checkpointed block,
+/// so the blocks missed while disconnected are backfilled before the chain is followed again.
+async fn index_with_reconnect(
+    config: &IndexerConfig,
+    indexer: &EvmEventIndexer,
+    transport: RpcTransport,
+    filter: Filter,
+    start_block: u64,
+    client: &mut tokio_postgres::Client,
+) {
+    let mut backoff = ReconnectBackoff::default();
+    let mut from_block = start_block;
+
+    loop {
+        if backoff.attempt() > 0 {
+            info!(
+                "Indexer {} reconnecting to {}, resuming from block {}",
+                config.event_name, config.rpc_url, from_block
+            );
+        }
+        let error = match connect_and_index(config, indexer, transport, &filter, from_block, client)
+            .await
+        {
+            Ok(()) => anyhow!("the indexer stopped"),
+            Err(error) => error,
+        };
+
+        // A dropped database connection is replaced as well, the checkpoint can't be read otherwise
+        if client.is_closed() {
+            match create_db_instance(&config.db_url).await {
+                Ok(new_client) => *client = new_client,
+                Err(db_error) => warn!(
+                    "Indexer {} could not reconnect to the database: {:#}",
+                    config.event_name, db_error
+                ),
+            }
+        }
+
+        // Resume right after the last checkpoint, the backoff starts over once indexing made progress
+        match get_checkpoint(client, &config.event_name).await {
+            Ok(Some(checkpoint)) => {
+                if checkpoint + 1 > from_block {
+                    backoff.reset();
+                }
+                from_block = checkpoint + 1;
+            }
+            Ok(None) => {}
+            Err(db_error) => warn!(
+                "Indexer {} could not read its checkpoint: {:#}",
+                config.event_name, db_error
+            ),
+        }
+
+        let delay = backoff.next_delay();
+        warn!(
+            "Indexer {} lost its connection to {}: {:#}, reconnecting in {:?} (attempt {})",
+            config.event_name,
+            config.rpc_url,
+            error,
+            delay,
+            backoff.attempt()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// This function connects to the RPC endpoint over `transport` and indexes from `from_block` onwards,
+/// websocket and ipc endpoints are subscribed to while HTTP endpoints are polled
+async fn connect_and_index(
+    config: &IndexerConfig,
+    indexer: &EvmEventIndexer,
+    transport: RpcTransport,
+    filter: &Filter,
+    from_block: u64,
+    client: &mut tokio_postgres::Client,
+) -> anyhow::Result<()> {
+    match transport {
+        RpcTransport::Ws => {
+            let ws = WsConnect::new(config.rpc_url.clone());
+            let provider = ProviderBuilder::new().on_ws(ws).await?;
+            indexer
+                .query_events(provider, filter.clone(), from_block.into(), client)
+                .await
+        }
+        RpcTransport::Ipc => {
+            let ipc = IpcConnect::new(ipc_path(&config.rpc_url));
+            let provider = ProviderBuilder::new().on_ipc(ipc).await?;
+            indexer
+                .query_events(provider, filter.clone(), from_block.into(), client)
+                .await
+        }
+        RpcTransport::Http => {
+            let provider = ProviderBuilder::new().on_http(config.rpc_url.parse()?);
+            let interval = config
+                .poll_interval_ms
+                .map_or(DEFAULT_POLL_INTERVAL, Duration::from_millis);
+            indexer
+                .poll_events(provider, filter.clone(), from_block, interval, client)
+                .await
+        }
+    }
+}
+
 impl IndexerTask {
-    pub fn new(config: IndexerConfig) -> Self {
-        Self { config }
+    pub fn new(config: IndexerConfig, rebuild: bool) -> Self {
+        Self { config, rebuild }
     }
 
     /// Converts the task into a boxed trait object.
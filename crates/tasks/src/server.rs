@@ -1,8 +1,11 @@
 use crate::Task;
 use anyhow::bail;
 use async_trait::async_trait;
-use chronicle_primitives::ServerConfig;
-use chronicle_server::{query::ChronicleQuery, run_chronicle_server};
+use chronicle_primitives::{abi::IndexerAbi, IndexerConfig, ServerConfig};
+use chronicle_server::{
+    query::ChronicleQuery, run_chronicle_server, subscription::ChronicleSubscription,
+    typed::TypedIndexer,
+};
 use tokio::{select, try_join};
 use tokio_util::sync::CancellationToken;
 use tracing::info;
@@ -10,17 +13,30 @@ use tracing::info;
 #[derive(Debug)]
 pub struct ServerTask {
     pub config: ServerConfig,
+    /// These are the indexers served, their ABIs describe the typed schema
+    pub indexers: Vec<IndexerConfig>,
 }
 
 #[async_trait]
 impl Task for ServerTask {
     async fn run(mut self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
+        let typed_indexers = self
+            .indexers
+            .iter()
+            .map(|config| {
+                Ok(TypedIndexer {
+                    name: config.event_name.clone(),
+                    abi: IndexerAbi::from_config(config)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
         let server_handle = tokio::spawn(async move {
             select! {
-                server = run_chronicle_server(self.config, ChronicleQuery) => {
+                server = run_chronicle_server(self.config, ChronicleQuery, ChronicleSubscription, typed_indexers) => {
                     // Want this indexing to halt before
-                    if server.is_err() {
-                        info!("GraphQL server failed to start");
+                    if let Err(e) = server {
+                        info!("GraphQL server failed to start: {e:?}");
                     }
                 }
                 _ = shutdown_token.cancelled() => {
@@ -40,8 +56,8 @@ impl Task for ServerTask {
 }
 
 impl ServerTask {
-    pub fn new(config: ServerConfig) -> Self {
-        Self { config }
+    pub fn new(config: ServerConfig, indexers: Vec<IndexerConfig>) -> Self {
+        Self { config, indexers }
     }
 
     /// Converts the task into a boxed trait object.